@@ -0,0 +1,74 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use std::time::Duration;
+
+use defender::sync::SyncGuard;
+use defender::GuardConfig;
+
+const WAITERS: usize = 8;
+
+fn config() -> GuardConfig {
+    GuardConfig {
+        poll_interval: Some(Duration::from_micros(10)),
+        ..Default::default()
+    }
+}
+
+fn sync_benches(c: &mut Criterion) {
+    let guard = SyncGuard::new(config());
+    assert!(guard.set(42u64).is_ok());
+    c.bench_function("sync/wait_set", |b| b.iter(|| black_box(guard.wait())));
+
+    // a poll of an unset guard reads its state tag without locking it
+    let unset = SyncGuard::<u64>::new(GuardConfig::INSTANT);
+    c.bench_function("sync/poll_unset", |b| b.iter(|| black_box(unset.wait())));
+
+    c.bench_function("sync/set_resolves_waiters", |b| {
+        b.iter(|| {
+            let guard = SyncGuard::new(config());
+            std::thread::scope(|scope| {
+                for _ in 0..WAITERS {
+                    scope.spawn(|| black_box(guard.wait()));
+                }
+                assert!(guard.set(42u64).is_ok());
+            });
+        })
+    });
+}
+
+#[cfg(feature = "async")]
+fn async_benches(c: &mut Criterion) {
+    use defender::r#async::AsyncGuard;
+
+    let rt = tokio::runtime::Builder::new_current_thread()
+        .build()
+        .unwrap();
+
+    let guard = AsyncGuard::new(config());
+    assert!(guard.set(42u64).is_ok());
+    c.bench_function("async/wait_set", |b| {
+        b.iter(|| rt.block_on(async { black_box(guard.wait().await) }))
+    });
+
+    c.bench_function("async/set_resolves_waiters", |b| {
+        b.iter(|| {
+            let guard = AsyncGuard::new(config());
+            rt.block_on(async {
+                let waits = futures::future::join_all((0..WAITERS).map(|_| guard.wait()));
+                let set = async {
+                    tokio::task::yield_now().await;
+                    assert!(guard.set(42u64).is_ok());
+                };
+                black_box(futures::future::join(waits, set).await)
+            })
+        })
+    });
+}
+
+fn benches(c: &mut Criterion) {
+    sync_benches(c);
+    #[cfg(feature = "async")]
+    async_benches(c);
+}
+
+criterion_group!(guard, benches);
+criterion_main!(guard);
@@ -0,0 +1,146 @@
+use std::any::Any;
+use std::sync::Arc;
+
+use crate::error::GuardError;
+use crate::sync::SyncGuard;
+
+trait ErasedGuard: Send + Sync {
+    fn wait_any(&self) -> Result<Arc<dyn Any + Send + Sync>, GuardError>;
+
+    fn clear(&self) -> Result<bool, GuardError>;
+
+    fn as_any(&self) -> &dyn Any;
+}
+
+impl<T: Send + Sync + 'static> ErasedGuard for SyncGuard<T> {
+    fn wait_any(&self) -> Result<Arc<dyn Any + Send + Sync>, GuardError> {
+        let value: Arc<dyn Any + Send + Sync> = self.wait()?;
+        Ok(value)
+    }
+
+    fn clear(&self) -> Result<bool, GuardError> {
+        SyncGuard::clear(self)
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+/// Type-erased handle of a `SyncGuard`, sharing the state of the guard it was created from.
+#[derive(Clone)]
+pub struct AnyGuard {
+    guard: Arc<dyn ErasedGuard>,
+}
+
+impl AnyGuard {
+    pub fn wait_any(&self) -> Result<Arc<dyn Any + Send + Sync>, GuardError> {
+        self.guard.wait_any()
+    }
+
+    /// Resets the guard without cloning the value out, see `SyncGuard::clear`.
+    pub fn clear(&self) -> Result<bool, GuardError> {
+        self.guard.clear()
+    }
+
+    pub fn downcast<T: Send + Sync + 'static>(&self) -> Option<SyncGuard<T>> {
+        self.guard.as_any().downcast_ref::<SyncGuard<T>>().cloned()
+    }
+}
+
+impl<T: Send + Sync + 'static> From<SyncGuard<T>> for AnyGuard {
+    fn from(guard: SyncGuard<T>) -> Self {
+        AnyGuard {
+            guard: Arc::new(guard),
+        }
+    }
+}
+
+/// Set of guards of any value type, handled together.
+#[derive(Clone, Default)]
+pub struct GuardGroup {
+    guards: Vec<AnyGuard>,
+}
+
+impl GuardGroup {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a guard to the group, sharing its state with `guard`.
+    pub fn push(&mut self, guard: impl Into<AnyGuard>) {
+        self.guards.push(guard.into());
+    }
+
+    /// Resets every guard of the group holding a value, killed guards are left as they are.
+    /// Returns the number of guards which were cleared.
+    pub fn reset_all(&self) -> Result<usize, GuardError> {
+        let mut cleared = 0;
+        for guard in &self.guards {
+            if guard.clear()? {
+                cleared += 1;
+            }
+        }
+
+        Ok(cleared)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::any::{AnyGuard, GuardGroup};
+    use crate::sync::SyncGuard;
+    use crate::GuardState;
+
+    #[test]
+    fn test_heterogeneous_guards() {
+        let number = SyncGuard::<u8>::default();
+        let text = SyncGuard::<String>::default();
+        let guards: Vec<AnyGuard> = vec![number.clone().into(), text.clone().into()];
+
+        assert!(number.set(42).is_ok());
+        assert!(text.set(String::from("Hello AnyGuard")).is_ok());
+
+        assert_eq!(
+            guards[0].wait_any().unwrap().downcast_ref::<u8>(),
+            Some(&42)
+        );
+        assert!(guards[0].downcast::<String>().is_none());
+        assert_eq!(*guards[0].downcast::<u8>().unwrap().wait().unwrap(), 42);
+
+        let text = guards[1].downcast::<String>().unwrap();
+        assert_eq!(text.wait().unwrap().as_str(), "Hello AnyGuard");
+        assert!(guards[1].downcast::<u8>().is_none());
+
+        // the value does not have to be `Clone`
+        let counter = SyncGuard::<std::sync::Mutex<u8>>::default();
+        let mut group = GuardGroup::new();
+        group.push(counter.clone());
+        assert!(counter.set(std::sync::Mutex::new(42)).is_ok());
+        let erased = AnyGuard::from(counter.clone());
+        let restored = erased.downcast::<std::sync::Mutex<u8>>().unwrap();
+        assert_eq!(*restored.wait().unwrap().lock().unwrap(), 42);
+        assert_eq!(group.reset_all().unwrap(), 1);
+    }
+
+    #[test]
+    fn test_group_reset_all() {
+        let number = SyncGuard::<u8>::default();
+        let text = SyncGuard::<String>::default();
+        let killed = SyncGuard::<u8>::default();
+        let mut group = GuardGroup::new();
+        group.push(number.clone());
+        group.push(text.clone());
+        group.push(killed.clone());
+
+        assert!(number.set(42).is_ok());
+        assert!(text.set(String::from("Hello GuardGroup")).is_ok());
+        assert!(killed.kill().is_ok());
+        assert_eq!(group.reset_all().unwrap(), 2);
+
+        assert!(!number.is_set());
+        assert!(!text.is_set());
+        assert_eq!(killed.state(), GuardState::Killed);
+        assert_eq!(group.reset_all().unwrap(), 0);
+    }
+}
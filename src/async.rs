@@ -1,124 +1,1420 @@
-use parking_lot::{Mutex, RwLock};
-use std::future::Future;
-use std::ops::{Deref, DerefMut};
+use std::future::{Future, IntoFuture};
+use std::ops::Deref;
 use std::pin::Pin;
+use std::sync::atomic::Ordering;
 use std::sync::Arc;
-use std::task::{Context, Poll};
+use std::task::{Context, Poll, Waker};
 
 use crate::error::GuardError;
-use crate::state::State;
-use crate::{GuardConfig, Timeout};
+use crate::inner::Inner;
+use crate::primitives::AtomicU64;
+use crate::state::{GuardReadRef, State, Waiter};
+use crate::sync::SyncGuard;
+use crate::timer::{self, Alarm};
+#[cfg(feature = "history")]
+use crate::GuardState;
+use crate::{GuardConfig, GuardStats, NotifyPolicy, Timeout};
 
-pub struct AsyncGuard<T: Clone> {
-    value: Arc<RwLock<State<T>>>,
-    config: GuardConfig,
-
-    t0: Arc<Mutex<Option<std::time::Instant>>>,
+pub struct AsyncGuard<T> {
+    inner: Inner<T>,
+    /// Generation last seen by `changed` through this handle.
+    seen: AtomicU64,
 }
 
-impl<T: Clone> Clone for AsyncGuard<T> {
+impl<T> Clone for AsyncGuard<T> {
     fn clone(&self) -> Self {
         AsyncGuard {
-            value: self.value.clone(),
-            config: self.config.clone(),
-            t0: self.t0.clone(),
+            inner: self.inner.clone(),
+            seen: AtomicU64::new(self.seen.load(Ordering::Acquire)),
+        }
+    }
+}
+
+impl<T> From<&SyncGuard<T>> for AsyncGuard<T> {
+    fn from(guard: &SyncGuard<T>) -> Self {
+        AsyncGuard {
+            inner: guard.inner.clone(),
+            seen: AtomicU64::new(guard.generation()),
         }
     }
 }
 
-impl<T: Clone> Default for AsyncGuard<T> {
+/// Guards are equal when they share the same state (i.e. one is a clone of the other), the
+/// guarded values are not compared.
+impl<T> PartialEq for AsyncGuard<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.inner.ptr_eq(&other.inner)
+    }
+}
+
+impl<T> Eq for AsyncGuard<T> {}
+
+impl<T> std::hash::Hash for AsyncGuard<T> {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.inner.as_ptr().hash(state)
+    }
+}
+
+impl<T> std::fmt::Debug for AsyncGuard<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.inner.fmt_debug(f, "AsyncGuard")
+    }
+}
+
+/// One-line summary, e.g. `AsyncGuard(name=cache, state=Set, timeout=500ms, waiters=3)`. The
+/// config is left out while it is locked for writing, so formatting never blocks.
+impl<T> std::fmt::Display for AsyncGuard<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.inner.fmt_display(f, "AsyncGuard")
+    }
+}
+
+impl<T> Default for AsyncGuard<T> {
     fn default() -> Self {
         AsyncGuard {
-            value: Arc::new(RwLock::default()),
-            config: GuardConfig::default(),
-            t0: Arc::new(Mutex::default()),
+            inner: Inner::default(),
+            seen: AtomicU64::default(),
         }
     }
 }
 
-impl<T: Clone> AsyncGuard<T> {
+impl<T> AsyncGuard<T> {
+    /// The config is shared by every clone of the guard.
     pub fn new(config: GuardConfig) -> Self {
         AsyncGuard {
-            config,
-            ..Default::default()
+            inner: Inner::new(config),
+            seen: AtomicU64::default(),
         }
     }
 }
 
-impl<T: Clone> AsyncGuard<T> {
+impl<T> AsyncGuard<T> {
+    /// Cancel safe: dropping the future before it resolves consumes no value and leaves the
+    /// guard untouched, so a later `wait` still sees the value.
     pub async fn wait(&self) -> Result<Arc<T>, GuardError> {
+        #[cfg(feature = "tracing")]
+        {
+            use tracing::Instrument;
+
+            let span = crate::trace::wait_span(self.inner.as_ptr(), &self.inner.config.read());
+            let t0 = std::time::Instant::now();
+            let result = self.into_future().instrument(span.clone()).await;
+            crate::trace::record_wait(&span, &result, t0);
+
+            result
+        }
+
+        #[cfg(not(feature = "tracing"))]
         self.await
     }
 
-    pub fn set(&mut self, value: T) -> Result<(), GuardError> {
-        match self.value.write().deref_mut() {
-            State::Killed => Err(GuardError::Killed),
-            state => {
-                *state = State::Value(Arc::new(value));
-                Ok(())
-            }
+    /// Borrows the current value holding the read lock of the guard until the returned
+    /// reference is dropped, fails with `NotReady` if the guard is unset.
+    pub fn borrow(&self) -> Result<GuardReadRef<'_, T>, GuardError> {
+        let state = self.inner.value.read();
+        if let State::Killed = state.deref() {
+            return Err(self.inner.killed());
         }
+        GuardReadRef::try_new(state).ok_or(GuardError::NotReady)
     }
 
-    pub fn kill(&mut self) -> Result<(), GuardError> {
-        match self.value.write().deref_mut() {
-            State::Value(_) => Err(GuardError::UnableToKilled),
-            state => {
-                *state = State::Killed;
-                Ok(())
-            }
+    /// Borrows the current value like `borrow`, marking it seen by this handle, so `changed`
+    /// only resolves on a transition after it.
+    pub fn borrow_and_update(&self) -> Result<GuardReadRef<'_, T>, GuardError> {
+        let state = self.inner.value.read();
+        self.seen.store(self.generation(), Ordering::Release);
+        if let State::Killed = state.deref() {
+            return Err(self.inner.killed());
         }
+        GuardReadRef::try_new(state).ok_or(GuardError::NotReady)
     }
 
-    pub fn reset(&mut self) -> Result<Option<T>, GuardError> {
-        let mut state = self.value.write();
+    /// Waits until the guard goes through a transition (set, modify, reset) not yet seen by this
+    /// handle, marking it seen. Fails with `Killed` once the guard is killed.
+    pub async fn changed(&self) -> Result<(), GuardError> {
+        ChangedFuture {
+            guard: self,
+            waker: None,
+        }
+        .await
+    }
 
-        match state.deref_mut() {
-            State::UnSet | State::Killed => Ok(None),
-            State::Value(val) => {
-                let value = (**val).to_owned();
-                *state = State::UnSet;
+    /// Waits at most `max` for the value whatever the timeout of the config, a missed value is
+    /// `None` rather than `Timeout`. Cancel safe like `wait`.
+    pub async fn recv_timeout(
+        &self,
+        max: std::time::Duration,
+    ) -> Result<Option<Arc<T>>, GuardError> {
+        let mut wait = self.into_future();
+        wait.timeout = Some(Timeout::Duration(max));
 
-                Ok(Some(value))
-            }
+        match wait.await {
+            Ok(value) => Ok(Some(value)),
+            Err(GuardError::Timeout) => Ok(None),
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Waits for a value satisfying `pred`, re-checking on every update of the guard. The
+    /// configured timeout applies to the whole wait, `pred` runs once per value.
+    pub async fn wait_while<F>(&self, pred: F) -> Result<Arc<T>, GuardError>
+    where
+        F: Fn(&T) -> bool,
+    {
+        WhileFuture {
+            guard: self,
+            pred: &pred,
+            waker: None,
+            t0: None,
+            polls: 0,
+            checked: None,
+            alarm: Alarm::default(),
+        }
+        .await
+    }
+
+    /// Waits once on behalf of every clone of the returned future, the resolved result is
+    /// handed to all of them without polling the guard again.
+    pub fn shared_wait(&self) -> impl Future<Output = Result<Arc<T>, GuardError>> + Clone + '_ {
+        futures::FutureExt::shared(self.wait())
+    }
+
+    /// Waits until the guard is killed, failing with `AlreadySet` if it gets a value instead.
+    /// Resolves to the message of the error the guard was killed with, if there was one.
+    pub async fn wait_for_kill(&self) -> Result<Option<String>, GuardError> {
+        KillFuture {
+            guard: self,
+            waker: None,
+            t0: None,
+            polls: 0,
+            alarm: Alarm::default(),
+        }
+        .await
+    }
+
+    /// Waits for the value and returns it along with the time spent waiting.
+    pub async fn wait_timed(&self) -> Result<(Arc<T>, std::time::Duration), GuardError> {
+        let t0 = std::time::Instant::now();
+        let value = self.wait().await?;
+
+        Ok((value, t0.elapsed()))
+    }
+
+    /// Waits for the value and projects it with `f`, no lock is held while waiting or projecting.
+    pub async fn wait_map_async<U, F>(&self, f: F) -> Result<U, GuardError>
+    where
+        F: FnOnce(&T) -> U,
+    {
+        let value = self.wait().await?;
+        Ok(f(&value))
+    }
+
+    /// Waits for the value, falling back to `default` on any error, so a timed out or killed
+    /// guard is indistinguishable from a guard holding `default`.
+    pub async fn wait_or(&self, default: T) -> Arc<T> {
+        self.wait_or_else(|| default).await
+    }
+
+    /// Waits for the value, falling back to the output of `f` on any error, so a timed out or
+    /// killed guard is indistinguishable from a guard holding the fallback.
+    pub async fn wait_or_else<F>(&self, f: F) -> Arc<T>
+    where
+        F: FnOnce() -> T,
+    {
+        match self.wait().await {
+            Ok(value) => value,
+            Err(_) => Arc::new(f()),
+        }
+    }
+
+    pub fn set(&self, value: T) -> Result<(), GuardError> {
+        self.set_arc(Arc::new(value))
+    }
+
+    /// Sets a value which is already behind an `Arc`, without moving it into a new one.
+    pub fn set_arc(&self, value: Arc<T>) -> Result<(), GuardError> {
+        self.inner.set_arc(value)
+    }
+
+    /// Sets the value like `set`, but hands it back whenever it is not stored: the guard is
+    /// killed, or it already has a value under `SetPolicy::RejectNewer` or `SetPolicy::Error`.
+    pub fn set_or_reject(&self, value: T) -> Result<(), T> {
+        self.inner.store(value).map_err(|(value, _)| value)
+    }
+
+    /// Awaits the future produced by `f` and stores its output, fails with `Killed` if the guard
+    /// was killed while the value was being computed.
+    pub async fn set_from<F, Fut>(&self, f: F) -> Result<(), GuardError>
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = T>,
+    {
+        let value = f().await;
+        self.set(value)
+    }
+
+    /// Sets the value unless it equals the current one, returning whether it was stored, so an
+    /// unchanged value neither advances the generation nor notifies anyone.
+    pub fn set_if_changed(&self, value: T) -> Result<bool, GuardError>
+    where
+        T: PartialEq,
+    {
+        self.inner.set_if_changed(value)
+    }
+
+    /// Sets the value only if the guard is still at `expected_generation`, returning the new
+    /// generation, or fails with `Conflict` when another transition happened in between.
+    pub fn compare_and_set(&self, expected_generation: u64, value: T) -> Result<u64, GuardError> {
+        self.inner.compare_and_set(expected_generation, value)
+    }
+
+    /// Fails with `UnableToKilled` if the guard already has a value, see `force_kill`.
+    pub fn kill(&self) -> Result<(), GuardError> {
+        self.inner.kill_as(None)
+    }
+
+    /// Kills the guard like `kill`, waiters fail with `Failed` carrying the message of `err`.
+    /// A guard which is already killed keeps the reason it was killed with first.
+    pub fn kill_with_error<E>(&self, err: E) -> Result<(), GuardError>
+    where
+        E: std::error::Error,
+    {
+        self.inner.kill_as(Some(err.to_string()))
+    }
+
+    /// Stores the value of a successful computation, or kills the guard with its error, so a
+    /// single call handles both outcomes of a fallible producer.
+    pub fn set_result<E>(&self, result: Result<T, E>) -> Result<(), GuardError>
+    where
+        E: std::error::Error,
+    {
+        match result {
+            Ok(value) => self.set(value),
+            Err(err) => self.kill_with_error(err),
+        }
+    }
+
+    /// Kills the guard whatever its state, dropping the value it holds, unlike `kill`.
+    pub fn force_kill(&self) -> Result<(), GuardError> {
+        self.inner.force_kill()
+    }
+
+    pub fn reset(&self) -> Result<Option<T>, GuardError>
+    where
+        T: Clone,
+    {
+        self.inner.reset()
+    }
+
+    /// Resets the guard like `reset` without cloning the value out, returning whether there was
+    /// a value to clear.
+    pub fn clear(&self) -> Result<bool, GuardError> {
+        self.inner.clear()
+    }
+
+    /// Resets the guard only if the current value satisfies `pred`, returning the cleared value.
+    pub fn reset_if<F>(&self, pred: F) -> Result<Option<T>, GuardError>
+    where
+        T: Clone,
+        F: FnOnce(&T) -> bool,
+    {
+        self.inner.reset_if(pred)
+    }
+
+    /// Applies `f` to the stored value in place, clones the value first if it is shared with an
+    /// ongoing reader.
+    pub fn modify<F>(&self, f: F) -> Result<(), GuardError>
+    where
+        T: Clone,
+        F: FnOnce(&mut T),
+    {
+        self.inner.modify(f)
+    }
+
+    /// Extracts the value consuming the guard, the value is moved out when this is the last
+    /// handle and no reader holds it, otherwise it is cloned.
+    pub fn try_into_inner(self) -> Result<Option<T>, GuardError>
+    where
+        T: Clone,
+    {
+        self.inner.try_into_inner()
+    }
+
+    /// Current value without waiting, `None` when the guard is either unset or killed.
+    pub fn get(&self) -> Option<Arc<T>> {
+        self.inner.get()
+    }
+
+    /// Creates a guard already holding `value`.
+    pub fn with_value(value: T, config: GuardConfig) -> Self {
+        AsyncGuard {
+            inner: Inner::with_value(value, config),
+            seen: AtomicU64::default(),
         }
     }
+
+    /// Creates a handle sharing the state of the guard, but with its own config derived from the
+    /// current one with `f`.
+    #[must_use = "the new handle is returned, the guard itself is left unchanged"]
+    pub fn map_config<F>(&self, f: F) -> Self
+    where
+        F: FnOnce(GuardConfig) -> GuardConfig,
+    {
+        AsyncGuard {
+            inner: self.inner.map_config(f),
+            seen: AtomicU64::new(self.seen.load(Ordering::Acquire)),
+        }
+    }
+
+    /// Creates an independent, unset guard with a copy of the config, unlike `clone` which
+    /// shares the state and the config.
+    #[must_use = "the new handle is returned, the guard itself is left unchanged"]
+    pub fn clone_empty(&self) -> Self {
+        AsyncGuard {
+            inner: self.inner.clone_empty(),
+            seen: AtomicU64::default(),
+        }
+    }
+
+    /// Changes the timeout for every clone of the guard, pending waits observe it on their next poll.
+    pub fn set_timeout(&self, timeout: Timeout) {
+        self.inner.set_timeout(timeout)
+    }
+
+    /// Deadline a wait starting now would give up at, `None` unless the timeout is a `Duration`,
+    /// so an external timer can be armed along with the guard's own timeout.
+    pub fn effective_deadline(&self) -> Option<std::time::Instant> {
+        self.inner.effective_deadline()
+    }
+
+    /// Number of value transitions (set, modify, reset) the guard has gone through.
+    pub fn generation(&self) -> u64 {
+        self.inner.generation()
+    }
+
+    /// Latest state transitions of the guard with the time they happened, oldest first, up to
+    /// `history_capacity` of them.
+    #[cfg(feature = "history")]
+    pub fn recent_transitions(&self) -> Vec<(std::time::Instant, GuardState)> {
+        self.inner.recent_transitions()
+    }
+
+    /// Registers `waker` to be woken once by the next set, kill or reset, the primitive the
+    /// futures of the guard are built on.
+    pub fn register_waker(&self, waker: Waker) {
+        self.inner.register_waker(waker)
+    }
+
+    /// Number of consumers currently waiting on the guard.
+    pub fn waiter_count(&self) -> usize {
+        self.inner.waiter_count()
+    }
+
+    /// Number of handles sharing the guard, including this one.
+    pub fn handle_count(&self) -> usize {
+        self.inner.handle_count()
+    }
+
+    /// Counters of the operations the guard went through, shared by all of its handles.
+    pub fn stats(&self) -> GuardStats {
+        self.inner.stats()
+    }
+
+    /// Raises the shutdown flag of the config, waking the pending waits so they fail with
+    /// `Cancelled`. Async waits notice a flag raised directly only once they are woken.
+    pub fn request_shutdown(&self) {
+        self.inner.request_shutdown()
+    }
+}
+
+/// Resolves with the index and result of the first of the `guards` to resolve, dropping the
+/// remaining waits. An empty `guards` resolves to `Killed` at index 0, like `sync::wait_any`.
+pub async fn wait_any<T>(guards: Vec<AsyncGuard<T>>) -> (usize, Result<Arc<T>, GuardError>) {
+    if guards.is_empty() {
+        return (0, Err(GuardError::Killed));
+    }
+    let waits = guards.iter().map(|guard| Box::pin(guard.wait()));
+    let (result, idx, _) = futures::future::select_all(waits).await;
+
+    (idx, result)
+}
+
+/// Resolves with the values of all the `guards` in order, or the first error, dropping the
+/// remaining waits.
+pub async fn wait_all<T>(guards: Vec<AsyncGuard<T>>) -> Result<Vec<Arc<T>>, GuardError> {
+    futures::future::try_join_all(guards.iter().map(|guard| guard.wait())).await
 }
 
-impl<T: Clone> Future for &AsyncGuard<T> {
+pub struct WaitFuture<'a, T> {
+    guard: &'a AsyncGuard<T>,
+    waiter: Option<Waiter<'a>>,
+    waker: Option<usize>,
+    t0: Option<std::time::Instant>,
+    /// Number of times the state was checked, counted against `Timeout::Attempts`.
+    polls: u32,
+    /// Timeout of the wait instead of the one of the config.
+    timeout: Option<Timeout>,
+    alarm: Alarm,
+}
+
+impl<'a, T> IntoFuture for &'a AsyncGuard<T> {
+    type Output = Result<Arc<T>, GuardError>;
+    type IntoFuture = WaitFuture<'a, T>;
+
+    fn into_future(self) -> Self::IntoFuture {
+        WaitFuture {
+            guard: self,
+            waiter: None,
+            waker: None,
+            t0: None,
+            polls: 0,
+            timeout: None,
+            alarm: Alarm::default(),
+        }
+    }
+}
+
+impl<T> Future for WaitFuture<'_, T> {
     type Output = Result<Arc<T>, GuardError>;
 
-    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
-        match self.config.timeout {
-            Timeout::Instant => match self.value.read().deref() {
-                State::Value(val) => Poll::Ready(Ok(val.clone())),
-                State::UnSet => Poll::Ready(Err(GuardError::Timeout)),
-                State::Killed => Poll::Ready(Err(GuardError::Killed)),
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let guard = self.guard;
+        if self.waiter.is_none() {
+            match Waiter::register(&guard.inner.waiters, guard.inner.config.read().max_waiters) {
+                Ok(waiter) => self.waiter = Some(waiter),
+                Err(err) => return Poll::Ready(Err(err)),
+            }
+            guard.inner.stats.waiting(guard.waiter_count());
+            #[cfg(feature = "metrics-rs")]
+            crate::metrics::waiters(&guard.inner.config.read(), guard.waiter_count());
+            guard.inner.initialize();
+        }
+        if guard.inner.config.read().is_shut_down() {
+            return Poll::Ready(Err(GuardError::Cancelled));
+        }
+
+        let t0 = *self.t0.get_or_insert_with(std::time::Instant::now);
+        let timeout = match self.timeout {
+            Some(timeout) => timeout,
+            None => guard.inner.config.read().timeout,
+        };
+        let poll = match timeout {
+            Timeout::Instant => match guard.inner.observe(0) {
+                State::Value(val) => Poll::Ready(Ok(val)),
+                State::UnSet if guard.inner.is_consumed() => Poll::Ready(Err(GuardError::Consumed)),
+                State::UnSet => Poll::Ready(Err(GuardError::NotReady)),
+                State::Killed => Poll::Ready(Err(guard.inner.killed())),
             },
-            Timeout::Infinite => match self.value.read().deref() {
-                State::Value(val) => Poll::Ready(Ok(val.clone())),
-                State::Killed => Poll::Ready(Err(GuardError::Killed)),
-                State::UnSet => {
-                    cx.waker().wake_by_ref();
-                    Poll::Pending
+            Timeout::AtLeast(min) if t0.elapsed() < min => {
+                // registered so that a kill ends the minimum early
+                guard
+                    .inner
+                    .wakers
+                    .lock()
+                    .register(&mut self.waker, cx.waker());
+                match guard.inner.inspect(guard.inner.value.read().deref()) {
+                    State::Killed => Poll::Ready(Err(guard.inner.killed())),
+                    _ => self.alarm.pending_until(Some(t0 + min), cx.waker()),
                 }
-            },
-            Timeout::Duration(timeout) => {
-                if self.t0.lock().is_none() {
-                    let t0 = std::time::Instant::now();
-                    *self.t0.lock().deref_mut() = Some(t0);
+            }
+            Timeout::Infinite
+            | Timeout::AtLeast(_)
+            | Timeout::Duration(_)
+            | Timeout::Attempts(_) => {
+                // registered before checking the state, so a set in between is not missed
+                guard
+                    .inner
+                    .wakers
+                    .lock()
+                    .register(&mut self.waker, cx.waker());
+                match guard.inner.observe(0) {
+                    State::Value(val) => Poll::Ready(Ok(val)),
+                    State::Killed => Poll::Ready(Err(guard.inner.killed())),
+                    State::UnSet if guard.inner.is_consumed() => {
+                        Poll::Ready(Err(GuardError::Consumed))
+                    }
+                    State::UnSet => {
+                        self.polls += 1;
+                        if timeout.is_exceeded(t0, self.polls) {
+                            Poll::Ready(Err(GuardError::Timeout))
+                        } else {
+                            let poll_interval = guard.inner.config.read().poll_interval;
+                            let at = timer::recheck_at(timeout, t0, poll_interval);
+                            self.alarm.pending_until(at, cx.waker())
+                        }
+                    }
                 }
+            }
+        };
+        if let Poll::Ready(result) = &poll {
+            guard.inner.stats.waited(result);
+            let elapsed = t0.elapsed();
+            // a resolved future no longer counts as a waiter, even if it is not dropped yet
+            self.waiter = None;
+            #[cfg(feature = "metrics-rs")]
+            {
+                let config = guard.inner.config.read();
+                crate::metrics::waited(&config, result, elapsed);
+                crate::metrics::waiters(&config, guard.waiter_count());
+            }
+            let slow_wait = guard.inner.config.read().slow_wait(result, elapsed);
+            if let Some(on_slow_wait) = slow_wait {
+                on_slow_wait(elapsed);
+            }
+        }
+
+        poll
+    }
+}
+
+impl<T> Drop for WaitFuture<'_, T> {
+    fn drop(&mut self) {
+        if let Some(id) = self.waker {
+            let mut wakers = self.guard.inner.wakers.lock();
+            let woken = !wakers.remove(id);
+            if woken && self.guard.inner.config.read().notify_policy != NotifyPolicy::All {
+                // the value this future was woken for is left for another waiter
+                wakers.wake_one();
+            }
+        }
+    }
+}
+
+struct KillFuture<'a, T> {
+    guard: &'a AsyncGuard<T>,
+    waker: Option<usize>,
+    t0: Option<std::time::Instant>,
+    polls: u32,
+    alarm: Alarm,
+}
+
+impl<T> Future for KillFuture<'_, T> {
+    type Output = Result<Option<String>, GuardError>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let guard = self.guard;
+        if guard.inner.config.read().is_shut_down() {
+            return Poll::Ready(Err(GuardError::Cancelled));
+        }
+        let t0 = *self.t0.get_or_insert_with(std::time::Instant::now);
+        let timeout = guard.inner.config.read().timeout;
+        if timeout != Timeout::Instant {
+            // registered before checking the state, so a kill in between is not missed
+            guard
+                .inner
+                .wakers
+                .lock()
+                .register(&mut self.waker, cx.waker());
+        }
+
+        match guard.inner.inspect(guard.inner.value.read().deref()) {
+            State::Killed => return Poll::Ready(Ok(guard.inner.failure.lock().clone())),
+            State::Value(_) => return Poll::Ready(Err(GuardError::AlreadySet)),
+            State::UnSet => {}
+        }
+        self.polls += 1;
+        match timeout {
+            Timeout::Instant => Poll::Ready(Err(GuardError::NotReady)),
+            timeout if timeout.is_exceeded(t0, self.polls) => Poll::Ready(Err(GuardError::Timeout)),
+            timeout => {
+                let poll_interval = guard.inner.config.read().poll_interval;
+                let at = timer::recheck_at(timeout, t0, poll_interval);
+                self.alarm.pending_until(at, cx.waker())
+            }
+        }
+    }
+}
+
+impl<T> Drop for KillFuture<'_, T> {
+    fn drop(&mut self) {
+        if let Some(id) = self.waker {
+            self.guard.inner.wakers.lock().remove(id);
+        }
+    }
+}
+
+struct ChangedFuture<'a, T> {
+    guard: &'a AsyncGuard<T>,
+    waker: Option<usize>,
+}
+
+impl<T> Future for ChangedFuture<'_, T> {
+    type Output = Result<(), GuardError>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let guard = self.guard;
+        if guard.inner.config.read().is_shut_down() {
+            return Poll::Ready(Err(GuardError::Cancelled));
+        }
+        guard
+            .inner
+            .wakers
+            .lock()
+            .register(&mut self.waker, cx.waker());
+
+        let generation = guard.generation();
+        if generation != guard.seen.load(Ordering::Acquire) {
+            guard.seen.store(generation, Ordering::Release);
+            return Poll::Ready(Ok(()));
+        }
+        match guard.inner.value.read().deref() {
+            State::Killed => Poll::Ready(Err(guard.inner.killed())),
+            _ => Poll::Pending,
+        }
+    }
+}
+
+impl<T> Drop for ChangedFuture<'_, T> {
+    fn drop(&mut self) {
+        if let Some(id) = self.waker {
+            self.guard.inner.wakers.lock().remove(id);
+        }
+    }
+}
+
+struct WhileFuture<'a, T, F> {
+    guard: &'a AsyncGuard<T>,
+    pred: &'a F,
+    waker: Option<usize>,
+    t0: Option<std::time::Instant>,
+    polls: u32,
+    /// Generation of the last value rejected by the predicate.
+    checked: Option<u64>,
+    alarm: Alarm,
+}
+
+impl<T, F: Fn(&T) -> bool> Future for WhileFuture<'_, T, F> {
+    type Output = Result<Arc<T>, GuardError>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let guard = self.guard;
+        if guard.inner.config.read().is_shut_down() {
+            return Poll::Ready(Err(GuardError::Cancelled));
+        }
+        let t0 = *self.t0.get_or_insert_with(std::time::Instant::now);
+        let timeout = guard.inner.config.read().timeout;
 
-                match self.t0.lock().deref() {
-                    Some(t0) if t0.elapsed() <= timeout => match self.value.read().deref() {
-                        State::Value(val) => Poll::Ready(Ok(val.clone())),
-                        State::Killed => Poll::Ready(Err(GuardError::Killed)),
-                        State::UnSet => Poll::Pending,
-                    },
-                    Some(_) => Poll::Ready(Err(GuardError::Timeout)),
-                    None => Poll::Pending,
+        // registered before checking the state, so an update in between is not missed
+        guard
+            .inner
+            .wakers
+            .lock()
+            .register(&mut self.waker, cx.waker());
+        if let Timeout::AtLeast(min) = timeout {
+            if t0.elapsed() < min {
+                if let State::Killed = guard.inner.inspect(guard.inner.value.read().deref()) {
+                    return Poll::Ready(Err(guard.inner.killed()));
                 }
+                return self.alarm.pending_until(Some(t0 + min), cx.waker());
+            }
+        }
+        let generation = guard.generation();
+        match guard.inner.inspect(guard.inner.value.read().deref()) {
+            State::Killed => return Poll::Ready(Err(guard.inner.killed())),
+            State::Value(val) if self.checked != Some(generation) && (self.pred)(val) => {
+                return Poll::Ready(Ok(val.clone()))
+            }
+            _ => self.checked = Some(generation),
+        }
+
+        self.polls += 1;
+        match timeout {
+            Timeout::Instant => Poll::Ready(Err(GuardError::NotReady)),
+            timeout if timeout.is_exceeded(t0, self.polls) => Poll::Ready(Err(GuardError::Timeout)),
+            timeout => {
+                let poll_interval = guard.inner.config.read().poll_interval;
+                let at = timer::recheck_at(timeout, t0, poll_interval);
+                self.alarm.pending_until(at, cx.waker())
+            }
+        }
+    }
+}
+
+impl<T, F> Drop for WhileFuture<'_, T, F> {
+    fn drop(&mut self) {
+        if let Some(id) = self.waker {
+            self.guard.inner.wakers.lock().remove(id);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::error::GuardError;
+    use crate::r#async::{wait_all, wait_any, AsyncGuard};
+    use crate::sync::SyncGuard;
+    use crate::{GuardConfig, NotifyPolicy, SetPolicy, Timeout};
+    use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn test_wait_map_async_projection() {
+        #[derive(Clone)]
+        struct Response {
+            status: u16,
+            _body: Vec<u8>,
+        }
+
+        let guard = AsyncGuard::<Response>::default();
+        let t_guard = guard.clone();
+        let producer = async move {
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            let response = Response {
+                status: 200,
+                _body: vec![0; 1024],
+            };
+            assert!(t_guard.set(response).is_ok());
+        };
+
+        let (status, _) = tokio::join!(guard.wait_map_async(|r| r.status), producer);
+        assert_eq!(status.unwrap(), 200);
+    }
+
+    #[tokio::test]
+    async fn test_wait_after_dropped_wait() {
+        let config = GuardConfig {
+            timeout: Timeout::Duration(Duration::from_millis(50)),
+            ..Default::default()
+        };
+        let guard = AsyncGuard::<u8>::new(config);
+
+        tokio::select! {
+            _ = tokio::time::sleep(Duration::from_millis(10)) => {}
+            _ = guard.wait() => panic!("guard should lose the race"),
+        }
+        tokio::time::sleep(Duration::from_millis(60)).await;
+
+        let t_guard = guard.clone();
+        let producer = async move {
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            assert!(t_guard.set(42).is_ok());
+        };
+        let (value, _) = tokio::join!(guard.wait(), producer);
+        assert_eq!(*value.unwrap(), 42);
+    }
+
+    #[tokio::test]
+    async fn test_max_waiters() {
+        let config = GuardConfig {
+            max_waiters: Some(1),
+            ..Default::default()
+        };
+        let guard = AsyncGuard::<u8>::new(config);
+        let producer = guard.clone();
+        let mut first = Box::pin(guard.wait());
+
+        tokio::select! {
+            biased;
+            _ = &mut first => panic!("guard should not be resolved"),
+            _ = tokio::time::sleep(Duration::from_millis(10)) => {}
+        }
+        assert_eq!(guard.waiter_count(), 1);
+        assert_eq!(guard.wait().await.unwrap_err(), GuardError::TooManyWaiters);
+
+        assert!(producer.set(42).is_ok());
+        assert_eq!(*(&mut first).await.unwrap(), 42);
+        // released once resolved, not only when dropped
+        assert_eq!(guard.waiter_count(), 0);
+        assert_eq!(*guard.wait().await.unwrap(), 42);
+        drop(first);
+    }
+
+    #[tokio::test]
+    async fn test_wait_any() {
+        let guards: Vec<_> = (0..3).map(|_| AsyncGuard::<u8>::default()).collect();
+        let t_guard = guards[2].clone();
+        let producer = async move {
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            assert!(t_guard.set(42).is_ok());
+        };
+
+        let ((idx, value), _) = tokio::join!(wait_any(guards.clone()), producer);
+        assert_eq!(idx, 2);
+        assert_eq!(*value.unwrap(), 42);
+        assert!(guards.iter().all(|guard| guard.waiter_count() == 0));
+
+        let (_, value) = wait_any(Vec::<AsyncGuard<u8>>::new()).await;
+        assert_eq!(value, Err(GuardError::Killed));
+    }
+
+    #[tokio::test]
+    async fn test_shared_wait_broadcasts_value() {
+        let guard = AsyncGuard::<u8>::default();
+        let shared = guard.shared_wait();
+        let waits = futures::future::join_all((0..1000).map(|_| shared.clone()));
+        let producer = async {
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            assert!(guard.set(42).is_ok());
+        };
+
+        let (values, _) = tokio::join!(waits, producer);
+        let value = shared.await.unwrap();
+        assert!(values
+            .into_iter()
+            .all(|other| Arc::ptr_eq(&other.unwrap(), &value)));
+        assert_eq!(guard.stats().resolved, 1);
+    }
+
+    #[tokio::test]
+    async fn test_sync_producer_async_consumer() {
+        let guard = SyncGuard::<u8>::default();
+        let consumer = guard.as_async();
+        let t_guard = guard.clone();
+        let producer = std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(20));
+            assert!(t_guard.set(42).is_ok());
+        });
+
+        assert_eq!(*consumer.wait().await.unwrap(), 42);
+        producer.join().expect("failed to join producer thread");
+
+        assert_eq!(consumer.reset(), Ok(Some(42)));
+        assert!(!guard.is_set());
+        assert!(consumer.set(7).is_ok());
+        assert_eq!(*guard.wait().unwrap(), 7);
+    }
+
+    #[tokio::test]
+    async fn test_guard_without_clone() {
+        struct Counter(AtomicUsize);
+        let sync_guard = crate::sync::SyncGuard::<Counter>::default();
+        let guard = sync_guard.as_async();
+        let counter = Arc::new(Counter(AtomicUsize::new(41)));
+        assert!(guard.set_arc(counter.clone()).is_ok());
+
+        let value = guard.wait().await.unwrap();
+        assert!(Arc::ptr_eq(&value, &counter));
+        value.0.fetch_add(1, Ordering::SeqCst);
+        assert_eq!(sync_guard.get().unwrap().0.load(Ordering::SeqCst), 42);
+    }
+
+    #[tokio::test]
+    async fn test_set_or_reject() {
+        let guard = AsyncGuard::<u8>::new(GuardConfig {
+            set_policy: SetPolicy::RejectNewer,
+            ..Default::default()
+        });
+        assert_eq!(guard.set_or_reject(1), Ok(()));
+        assert_eq!(guard.set_or_reject(2), Err(2));
+        assert_eq!(*guard.wait().await.unwrap(), 1);
+
+        assert!(guard.force_kill().is_ok());
+        assert_eq!(guard.set_or_reject(3), Err(3));
+    }
+
+    #[tokio::test]
+    async fn test_as_async_shares_state() {
+        use crate::sync::WaitOutcome;
+
+        let config = GuardConfig {
+            timeout: Timeout::Duration(Duration::from_millis(10)),
+            serve_stale_on_timeout: true,
+            ..Default::default()
+        };
+        let guard = SyncGuard::from_fn(|| 42u8, config);
+        let consumer = guard.as_async();
+        assert_eq!(*consumer.wait().await.unwrap(), 42);
+
+        assert_eq!(consumer.reset(), Ok(Some(42)));
+        assert_eq!(guard.wait_outcome(), Ok(WaitOutcome::Stale(Arc::new(42))));
+
+        #[cfg(feature = "crossbeam")]
+        {
+            let ready = guard.ready_receiver();
+            assert!(consumer.set(7).is_ok());
+            assert_eq!(ready.try_recv().as_deref(), Ok(&7));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_wait_all() {
+        let guards: Vec<_> = (0..3).map(|_| AsyncGuard::<u8>::default()).collect();
+        let t_guards = guards.clone();
+        let producer = async move {
+            for (idx, guard) in t_guards.iter().enumerate().rev() {
+                tokio::time::sleep(Duration::from_millis(20)).await;
+                assert!(guard.set(idx as u8).is_ok());
             }
+        };
+
+        let (values, _) = tokio::join!(wait_all(guards.clone()), producer);
+        let values: Vec<_> = values.unwrap().iter().map(|value| **value).collect();
+        assert_eq!(values, vec![0, 1, 2]);
+        assert!(guards.iter().all(|guard| guard.waiter_count() == 0));
+
+        assert!(guards[1].reset().is_ok());
+        assert!(guards[1].kill().is_ok());
+        assert_eq!(wait_all(guards).await.unwrap_err(), GuardError::Killed);
+    }
+
+    #[tokio::test]
+    async fn test_wait_cancel_safe() {
+        let guard = AsyncGuard::<u8>::default();
+        assert!(guard.set(42).is_ok());
+        let generation = guard.generation();
+
+        tokio::select! {
+            biased;
+            _ = std::future::ready(()) => {}
+            _ = guard.wait() => panic!("wait should lose the biased race"),
         }
+
+        assert_eq!(guard.waiter_count(), 0);
+        assert_eq!(guard.generation(), generation);
+        assert_eq!(*guard.wait().await.unwrap(), 42);
+    }
+
+    #[tokio::test]
+    async fn test_notify_one_hands_off_value() {
+        let guard = AsyncGuard::<u8>::new(GuardConfig {
+            notify_policy: NotifyPolicy::One,
+            ..Default::default()
+        });
+        let producer = async {
+            for value in 1..=2 {
+                tokio::time::sleep(Duration::from_millis(20)).await;
+                assert!(guard.set(value).is_ok());
+            }
+        };
+
+        let (first, second, _) = tokio::join!(guard.wait(), guard.wait(), producer);
+        let mut values = vec![*first.unwrap(), *second.unwrap()];
+        values.sort();
+        assert_eq!(values, vec![1, 2]);
+        assert_eq!(guard.waiter_count(), 0);
+    }
+
+    #[test]
+    fn test_duration_timeout_without_timer() {
+        let guard = AsyncGuard::<u8>::new(GuardConfig {
+            timeout: Timeout::Duration(Duration::from_millis(30)),
+            ..Default::default()
+        });
+
+        let t0 = std::time::Instant::now();
+        let result = futures::executor::block_on(guard.wait());
+        assert_eq!(result.unwrap_err(), GuardError::Timeout);
+        assert!(t0.elapsed() >= Duration::from_millis(30));
+    }
+
+    #[tokio::test]
+    async fn test_compare_and_set() {
+        let guard = AsyncGuard::<u8>::default();
+        let generation = guard.generation();
+        let next = guard.compare_and_set(generation, 1).unwrap();
+        assert_eq!(next, generation + 1);
+
+        assert_eq!(
+            guard.compare_and_set(generation, 2).unwrap_err(),
+            GuardError::Conflict
+        );
+        assert_eq!(*guard.wait().await.unwrap(), 1);
+
+        assert_eq!(guard.compare_and_set(next, 2), Ok(next + 1));
+        assert_eq!(*guard.wait().await.unwrap(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_borrow_and_changed() {
+        let guard = AsyncGuard::<String>::default();
+        assert_eq!(guard.borrow().err(), Some(GuardError::NotReady));
+        assert!(guard.set(String::from("first")).is_ok());
+        assert!(guard.changed().await.is_ok());
+        assert_eq!(*guard.borrow().unwrap(), "first");
+
+        let t_guard = guard.clone();
+        let producer = async move {
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            assert!(t_guard.set(String::from("second")).is_ok());
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            assert!(t_guard.reset().is_ok());
+            assert!(t_guard.kill().is_ok());
+        };
+        let consumer = async {
+            assert!(guard.changed().await.is_ok());
+            assert_eq!(*guard.borrow().unwrap(), "second");
+            assert!(guard.changed().await.is_ok());
+            assert_eq!(guard.changed().await, Err(GuardError::Killed));
+        };
+        tokio::join!(producer, consumer);
+    }
+
+    #[tokio::test]
+    async fn test_borrow_and_update() {
+        use futures::FutureExt;
+
+        let guard = AsyncGuard::<String>::default();
+        assert!(guard.set(String::from("first")).is_ok());
+        assert_eq!(*guard.borrow_and_update().unwrap(), "first");
+        assert_eq!(guard.changed().now_or_never(), None);
+
+        assert!(guard.set(String::from("second")).is_ok());
+        assert!(guard.changed().await.is_ok());
+        assert_eq!(*guard.borrow_and_update().unwrap(), "second");
+
+        assert!(guard.set(String::from("third")).is_ok());
+        assert_eq!(*guard.borrow_and_update().unwrap(), "third");
+        assert_eq!(guard.changed().now_or_never(), None);
+    }
+
+    #[tokio::test]
+    async fn test_set_if_changed_wakes_once() {
+        let guard = AsyncGuard::<u8>::default();
+        let changes = AtomicUsize::new(0);
+        let t_guard = guard.clone();
+        let producer = async move {
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            assert_eq!(t_guard.set_if_changed(1), Ok(true));
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            assert_eq!(t_guard.set_if_changed(1), Ok(false));
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            assert!(t_guard.kill().is_err());
+            assert!(t_guard.force_kill().is_ok());
+        };
+        let consumer = async {
+            while guard.changed().await.is_ok() {
+                changes.fetch_add(1, Ordering::AcqRel);
+            }
+        };
+        tokio::join!(producer, consumer);
+        // the set of 1 and the force kill dropping it
+        assert_eq!(changes.load(Ordering::Acquire), 2);
+        assert_eq!(guard.generation(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_wait_while_predicate_holds() {
+        let guard = AsyncGuard::<u8>::default();
+        let checks = AtomicUsize::new(0);
+        let t_guard = guard.clone();
+        let producer = async move {
+            for value in 1..=3 {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+                assert!(t_guard.set(value).is_ok());
+            }
+        };
+        let consumer = guard.wait_while(|value| {
+            checks.fetch_add(1, Ordering::AcqRel);
+            *value >= 3
+        });
+
+        let (value, _) = tokio::join!(consumer, producer);
+        assert_eq!(*value.unwrap(), 3);
+        assert_eq!(checks.load(Ordering::Acquire), 3);
+
+        guard.set_timeout(Timeout::Duration(Duration::from_millis(30)));
+        assert_eq!(
+            guard.wait_while(|value| *value > 3).await,
+            Err(GuardError::Timeout)
+        );
+    }
+
+    #[test]
+    fn test_register_waker_woken_once() {
+        struct CountingWaker(AtomicUsize);
+
+        impl std::task::Wake for CountingWaker {
+            fn wake(self: Arc<Self>) {
+                self.0.fetch_add(1, Ordering::AcqRel);
+            }
+        }
+
+        let guard = AsyncGuard::<u8>::default();
+        let counter = Arc::new(CountingWaker(AtomicUsize::new(0)));
+        guard.register_waker(counter.clone().into());
+        assert_eq!(counter.0.load(Ordering::Acquire), 0);
+
+        assert!(guard.set(1).is_ok());
+        assert!(guard.set(2).is_ok());
+        assert_eq!(counter.0.load(Ordering::Acquire), 1);
+
+        guard.register_waker(counter.clone().into());
+        assert!(guard.reset().is_ok());
+        assert_eq!(counter.0.load(Ordering::Acquire), 2);
+    }
+
+    #[tokio::test]
+    async fn test_wait_timed() {
+        let guard = AsyncGuard::<u8>::default();
+        let t_guard = guard.clone();
+        let producer = async move {
+            tokio::time::sleep(Duration::from_millis(100)).await;
+            assert!(t_guard.set(42).is_ok());
+        };
+
+        let (result, _) = tokio::join!(guard.wait_timed(), producer);
+        let (value, elapsed) = result.unwrap();
+        assert_eq!(*value, 42);
+        assert!(elapsed >= Duration::from_millis(100));
+        assert!(elapsed <= Duration::from_millis(120));
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_wait_deadlines() {
+        let guard = AsyncGuard::<u8>::new(GuardConfig {
+            timeout: Timeout::Duration(Duration::from_millis(100)),
+            ..Default::default()
+        });
+        let other = guard.map_config(|config| GuardConfig {
+            timeout: Timeout::Duration(Duration::from_millis(80)),
+            ..config
+        });
+
+        let first = async {
+            let t0 = std::time::Instant::now();
+            (guard.wait().await, t0.elapsed())
+        };
+        let second = async {
+            tokio::time::sleep(Duration::from_millis(40)).await;
+            let t0 = std::time::Instant::now();
+            (other.wait().await, t0.elapsed())
+        };
+
+        let ((first, first_elapsed), (second, second_elapsed)) = tokio::join!(first, second);
+        assert_eq!(first.unwrap_err(), GuardError::Timeout);
+        assert!(first_elapsed >= Duration::from_millis(100));
+        assert_eq!(second.unwrap_err(), GuardError::Timeout);
+        assert!(second_elapsed >= Duration::from_millis(80));
+    }
+
+    #[tokio::test]
+    async fn test_kill_wakes_parked_waiter() {
+        let polls = Arc::new(AtomicUsize::new(0));
+        let t_polls = polls.clone();
+        let guard = AsyncGuard::<u8>::new(GuardConfig {
+            on_poll: Some(Arc::new(move |_| {
+                t_polls.fetch_add(1, Ordering::SeqCst);
+            })),
+            ..Default::default()
+        });
+        let t_guard = guard.clone();
+        let _t = std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(50));
+            assert!(t_guard.kill().is_ok());
+        });
+
+        let t0 = std::time::Instant::now();
+        assert_eq!(guard.wait().await.unwrap_err(), GuardError::Killed);
+        assert!(t0.elapsed() < Duration::from_millis(60));
+        assert_eq!(polls.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn test_instant_wait_not_ready() {
+        let guard = AsyncGuard::<u8>::new(GuardConfig {
+            timeout: Timeout::Instant,
+            ..Default::default()
+        });
+        assert_eq!(guard.wait().await.unwrap_err(), GuardError::NotReady);
+        assert!(guard.set(42).is_ok());
+        assert_eq!(*guard.wait().await.unwrap(), 42);
+    }
+
+    #[tokio::test]
+    async fn test_wait_for_kill() {
+        let guard = AsyncGuard::<u8>::default();
+        let t_guard = guard.clone();
+        let _t = std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(50));
+            assert!(t_guard.kill().is_ok());
+        });
+
+        let t0 = std::time::Instant::now();
+        assert_eq!(guard.wait_for_kill().await, Ok(None));
+        assert!(t0.elapsed() < Duration::from_millis(60));
+
+        let guard = AsyncGuard::<u8>::new(GuardConfig::INSTANT);
+        assert_eq!(guard.wait_for_kill().await, Err(GuardError::NotReady));
+    }
+
+    #[tokio::test]
+    async fn test_set_result_err_fails_waiters() {
+        let guard = AsyncGuard::<u8>::default();
+        let t_guard = guard.clone();
+        let _t = std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(20));
+            let result = Err(std::io::Error::other("upstream down"));
+            assert!(t_guard.set_result(result).is_ok());
+        });
+
+        let failed = GuardError::Failed(String::from("upstream down"));
+        assert_eq!(guard.wait().await, Err(failed));
+        assert!(guard.kill().is_ok());
+        assert_eq!(
+            guard.wait_for_kill().await,
+            Ok(Some(String::from("upstream down")))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_wait_cancelled_by_shutdown_flag() {
+        let shutdown = Arc::new(AtomicBool::new(false));
+        let guard = AsyncGuard::<u8>::new(GuardConfig {
+            shutdown: Some(shutdown.clone()),
+            ..Default::default()
+        });
+        let polls = Arc::new(AtomicUsize::new(0));
+        let t_polls = polls.clone();
+        let guard = guard.map_config(|config| GuardConfig {
+            on_poll: Some(Arc::new(move |_| {
+                t_polls.fetch_add(1, Ordering::SeqCst);
+            })),
+            ..config
+        });
+        let t_guard = guard.clone();
+        let _t = std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(50));
+            t_guard.request_shutdown();
+        });
+
+        assert_eq!(guard.wait().await.unwrap_err(), GuardError::Cancelled);
+        assert!(shutdown.load(Ordering::Acquire));
+        // the waiter stays parked until the shutdown wakes it
+        assert_eq!(polls.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn test_wait_timeout_without_runtime() {
+        let polls = Arc::new(AtomicUsize::new(0));
+        let t_polls = polls.clone();
+        let guard = AsyncGuard::<u8>::new(GuardConfig {
+            timeout: Timeout::Duration(Duration::from_millis(50)),
+            on_poll: Some(Arc::new(move |_| {
+                t_polls.fetch_add(1, Ordering::SeqCst);
+            })),
+            ..Default::default()
+        });
+
+        let t0 = std::time::Instant::now();
+        assert_eq!(
+            futures::executor::block_on(guard.wait()),
+            Err(GuardError::Timeout)
+        );
+        assert!(t0.elapsed() >= Duration::from_millis(50));
+        // polled once up front and once when the deadline is due
+        assert!(polls.load(Ordering::SeqCst) <= 3);
+
+        polls.store(0, Ordering::SeqCst);
+        let guard = guard.map_config(|config| GuardConfig {
+            timeout: Timeout::Attempts(3),
+            poll_interval: Some(Duration::from_millis(10)),
+            ..config
+        });
+        assert_eq!(
+            futures::executor::block_on(guard.wait_for_kill()),
+            Err(GuardError::Timeout)
+        );
+        assert_eq!(polls.load(Ordering::SeqCst), 3);
+    }
+
+    #[test]
+    fn test_recv_timeout_without_runtime() {
+        let guard = AsyncGuard::<u8>::default();
+        let max = Duration::from_millis(20);
+        assert_eq!(
+            futures::executor::block_on(guard.recv_timeout(max)),
+            Ok(None)
+        );
+    }
+
+    #[test]
+    fn test_wait_at_least_without_runtime() {
+        let polls = Arc::new(AtomicUsize::new(0));
+        let t_polls = polls.clone();
+        let guard = AsyncGuard::<u8>::new(GuardConfig {
+            timeout: Timeout::AtLeast(Duration::from_millis(50)),
+            on_poll: Some(Arc::new(move |_| {
+                t_polls.fetch_add(1, Ordering::SeqCst);
+            })),
+            ..Default::default()
+        });
+        assert!(guard.set(42).is_ok());
+
+        let t0 = std::time::Instant::now();
+        assert_eq!(*futures::executor::block_on(guard.wait()).unwrap(), 42);
+        assert!(t0.elapsed() >= Duration::from_millis(50));
+        // the minimum is waited for with the timer, not by re-polling
+        assert!(polls.load(Ordering::SeqCst) <= 3);
+    }
+
+    #[tokio::test]
+    async fn test_recv_timeout() {
+        let guard = AsyncGuard::<u8>::new(GuardConfig::INSTANT);
+        assert_eq!(
+            guard.recv_timeout(Duration::from_millis(20)).await,
+            Ok(None)
+        );
+
+        let t_guard = guard.clone();
+        let producer = async move {
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            assert!(t_guard.set(42).is_ok());
+        };
+        let (value, _) = tokio::join!(guard.recv_timeout(Duration::from_millis(200)), producer);
+        assert_eq!(*value.unwrap().unwrap(), 42);
+
+        let guard = guard.clone_empty();
+        let t_guard = guard.clone();
+        let producer = async move {
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            assert!(t_guard.kill().is_ok());
+        };
+        let (value, _) = tokio::join!(guard.recv_timeout(Duration::from_millis(200)), producer);
+        assert_eq!(value, Err(GuardError::Killed));
+        assert_eq!(guard.waiter_count(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_wait_or_default_on_timeout() {
+        let config = GuardConfig {
+            timeout: Timeout::Duration(Duration::from_millis(50)),
+            ..Default::default()
+        };
+        let guard = AsyncGuard::<u8>::new(config);
+        assert_eq!(*guard.wait_or(42).await, 42);
+    }
+
+    #[tokio::test]
+    async fn test_wait_or_else_default_on_kill() {
+        let guard = AsyncGuard::<u8>::default();
+        assert!(guard.kill().is_ok());
+        assert_eq!(*guard.wait_or_else(|| 42).await, 42);
+
+        let guard = AsyncGuard::<u8>::default();
+        assert!(guard.set(7).is_ok());
+        assert_eq!(*guard.wait_or_else(|| 42).await, 7);
+    }
+
+    #[tokio::test]
+    async fn test_set_from_async_producer() {
+        let guard = AsyncGuard::<u8>::default();
+        let (set, value) = tokio::join!(
+            guard.set_from(|| async {
+                tokio::time::sleep(Duration::from_millis(50)).await;
+                42
+            }),
+            guard.wait()
+        );
+
+        assert!(set.is_ok());
+        assert_eq!(*value.unwrap(), 42);
+    }
+
+    #[tokio::test]
+    async fn test_set_from_killed_guard() {
+        let guard = AsyncGuard::<u8>::default();
+        assert!(guard.kill().is_ok());
+
+        let result = guard.set_from(|| async { 42 }).await;
+        assert!(matches!(result, Err(GuardError::Killed)));
+    }
+
+    #[test]
+    fn test_guard_is_send_sync() {
+        fn assert_send_sync<T: Send + Sync>() {}
+
+        assert_send_sync::<AsyncGuard<u8>>();
+        assert_send_sync::<AsyncGuard<String>>();
     }
 }
@@ -0,0 +1,31 @@
+use ::bytes::Bytes;
+
+use crate::error::GuardError;
+use crate::sync::SyncGuard;
+
+pub type BytesGuard = SyncGuard<Bytes>;
+
+impl SyncGuard<Bytes> {
+    /// Waits for the buffer and returns a cheap, reference counted clone of it, sharing the
+    /// memory of the buffer that was set.
+    pub fn wait_bytes(&self) -> Result<Bytes, GuardError> {
+        self.wait().map(|value| Bytes::clone(&value))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::bytes::BytesGuard;
+    use ::bytes::Bytes;
+
+    #[test]
+    fn test_wait_bytes_shares_buffer() {
+        let buffer = Bytes::from(vec![42u8; 1024]);
+        let guard = BytesGuard::default();
+        assert!(guard.set(buffer.clone()).is_ok());
+
+        let value = guard.wait_bytes().unwrap();
+        assert_eq!(value, buffer);
+        assert_eq!(value.as_ptr(), buffer.as_ptr());
+    }
+}
@@ -1,19 +1,729 @@
-#[derive(Clone)]
+use std::borrow::Cow;
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+use crate::error::GuardError;
+use crate::primitives;
+use crate::GuardState;
+
+pub type PollHook = Arc<dyn Fn(&GuardState) + Send + Sync>;
+pub type SlowWaitHook = Arc<dyn Fn(std::time::Duration) + Send + Sync>;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum Timeout {
     Instant,
+    /// Gives up once this much time passed since the wait began. Pauses between state checks
+    /// are cut short at the deadline, so even sub-millisecond timeouts are honoured within the
+    /// sleep granularity of the OS rather than overshooting by the poll interval.
     Duration(std::time::Duration),
     Infinite,
+    /// Waits without a deadline but returns the value no earlier than this after the wait
+    /// began, a kill still returns immediately.
+    AtLeast(std::time::Duration),
+    /// Gives up after this many state checks, pausing by the wait strategy between them,
+    /// independently of the wall clock.
+    Attempts(u32),
+}
+
+/// Error of parsing a `Timeout` from a string.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+#[error("Invalid timeout: {0:?}")]
+pub struct ParseTimeoutError(String);
+
+impl Timeout {
+    /// The stricter of the two timeouts, ordered from the strictest to the loosest:
+    /// - `Instant`, which never waits.
+    /// - `Attempts`, fewer first. The time they take depends on the wait strategy, which is not
+    ///   known here, so any number of attempts counts as stricter than a `Duration`.
+    /// - `Duration`, shorter first.
+    /// - `AtLeast`, shorter first, since it waits without a deadline.
+    /// - `Infinite`, the loosest.
+    pub fn min(self, other: Timeout) -> Timeout {
+        if self.strictness() <= other.strictness() {
+            self
+        } else {
+            other
+        }
+    }
+
+    fn strictness(&self) -> (u8, std::time::Duration) {
+        match *self {
+            Timeout::Instant => (0, std::time::Duration::ZERO),
+            Timeout::Attempts(attempts) => (1, std::time::Duration::from_nanos(attempts.into())),
+            Timeout::Duration(duration) => (2, duration),
+            Timeout::AtLeast(duration) => (3, duration),
+            Timeout::Infinite => (4, std::time::Duration::ZERO),
+        }
+    }
+
+    /// Deadline of a wait beginning at `t0`, if the timeout has one.
+    pub(crate) fn deadline(&self, t0: std::time::Instant) -> Option<std::time::Instant> {
+        match *self {
+            Timeout::Duration(timeout) => t0.checked_add(timeout),
+            _ => None,
+        }
+    }
+
+    /// Time left until the deadline of a wait which began at `t0`, if the timeout has one.
+    pub(crate) fn remaining(&self, t0: std::time::Instant) -> Option<std::time::Duration> {
+        match *self {
+            Timeout::Duration(timeout) => Some(timeout.saturating_sub(t0.elapsed())),
+            _ => None,
+        }
+    }
+
+    /// Whether a wait which began at `t0` and checked the state `polls` times is over.
+    pub(crate) fn is_exceeded(&self, t0: std::time::Instant, polls: u32) -> bool {
+        match *self {
+            Timeout::Duration(timeout) => t0.elapsed() > timeout,
+            Timeout::Attempts(attempts) => polls >= attempts,
+            Timeout::Instant | Timeout::Infinite | Timeout::AtLeast(_) => false,
+        }
+    }
+}
+
+/// Parses `instant`, `infinite`, a duration as an integer with a `ns`, `us`, `ms`, `s`, `m` or
+/// `h` suffix, e.g. `500ms`, a minimum as `at least` and a duration, e.g. `at least 1s`, or a
+/// number of attempts, e.g. `5 attempts`.
+impl std::str::FromStr for Timeout {
+    type Err = ParseTimeoutError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || ParseTimeoutError(s.to_owned());
+        let trimmed = s.trim();
+        match trimmed {
+            "instant" => return Ok(Timeout::Instant),
+            "infinite" => return Ok(Timeout::Infinite),
+            _ => {}
+        }
+        if let Some(min) = trimmed.strip_prefix("at least ") {
+            return parse_duration(min)
+                .map(Timeout::AtLeast)
+                .ok_or_else(invalid);
+        }
+        if let Some(attempts) = trimmed.strip_suffix(" attempts") {
+            // `u32::from_str` would accept a leading `+`
+            if !attempts.bytes().all(|b| b.is_ascii_digit()) {
+                return Err(invalid());
+            }
+            return attempts
+                .parse()
+                .map(Timeout::Attempts)
+                .map_err(|_| invalid());
+        }
+
+        parse_duration(trimmed)
+            .map(Timeout::Duration)
+            .ok_or_else(invalid)
+    }
+}
+
+fn parse_duration(s: &str) -> Option<std::time::Duration> {
+    let split = s.find(|c: char| !c.is_ascii_digit())?;
+    let (amount, unit) = s.split_at(split);
+    let amount: u64 = amount.parse().ok()?;
+    let duration = match unit {
+        "ns" => std::time::Duration::from_nanos(amount),
+        "us" => std::time::Duration::from_micros(amount),
+        "ms" => std::time::Duration::from_millis(amount),
+        "s" => std::time::Duration::from_secs(amount),
+        "m" => std::time::Duration::from_secs(amount.checked_mul(60)?),
+        "h" => std::time::Duration::from_secs(amount.checked_mul(3600)?),
+        _ => return None,
+    };
+    Some(duration)
+}
+
+/// Formats a duration in milliseconds, e.g. `500ms`, or in `us` or `ns` when it is not a whole
+/// number of milliseconds. Every timeout parses back from its output, e.g. `at least 1000ms` or
+/// `5 attempts`.
+impl std::fmt::Display for Timeout {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Timeout::Instant => write!(f, "instant"),
+            Timeout::Infinite => write!(f, "infinite"),
+            Timeout::Duration(duration) => fmt_duration(f, duration),
+            Timeout::AtLeast(duration) => {
+                write!(f, "at least ")?;
+                fmt_duration(f, duration)
+            }
+            Timeout::Attempts(attempts) => write!(f, "{attempts} attempts"),
+        }
+    }
+}
+
+fn fmt_duration(
+    f: &mut std::fmt::Formatter<'_>,
+    duration: &std::time::Duration,
+) -> std::fmt::Result {
+    match duration.subsec_nanos() {
+        nanos if nanos % 1_000_000 == 0 => write!(f, "{}ms", duration.as_millis()),
+        nanos if nanos % 1_000 == 0 => write!(f, "{}us", duration.as_micros()),
+        _ => write!(f, "{}ns", duration.as_nanos()),
+    }
+}
+
+impl TryFrom<&str> for Timeout {
+    type Error = ParseTimeoutError;
+
+    fn try_from(s: &str) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+/// Decides what `set` does when the guard already has a value.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum SetPolicy {
+    /// Replace the existing value.
+    #[default]
+    Overwrite,
+    /// Keep the existing value, `set` drops the new one while `set_or_reject` hands it back.
+    RejectNewer,
+    /// Keep the existing value and fail with `AlreadySet`.
+    Error,
+}
+
+/// Decides how many waiters a set value is delivered to.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum NotifyPolicy {
+    /// Every waiter receives the value.
+    #[default]
+    All,
+    /// The value is consumed by the first `wait` observing it, resetting the guard, so the other
+    /// waiters keep waiting for the next value.
+    One,
+    /// Like `One`, but a late waiter does not wait for another value: once the value was
+    /// consumed, waits fail with `Consumed` until the guard is set again.
+    Once,
+}
+
+/// Decides how a blocking wait pauses between state checks.
+#[derive(Clone, Copy, Debug, Default)]
+pub enum WaitStrategy {
+    /// Pause by `poll_interval` between checks, or spin if it is not set.
+    #[default]
+    Fixed,
+    /// Pause `start` first, then grow the pause by `factor` (at least `1.0`) up to `max`.
+    /// Each pause is shortened by a random fraction of up to `jitter` (`0.0` to `1.0`), so
+    /// waiters sharing a guard do not check it in lockstep.
+    Backoff {
+        start: std::time::Duration,
+        max: std::time::Duration,
+        factor: f64,
+        jitter: f64,
+    },
 }
 
+impl PartialEq for WaitStrategy {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (WaitStrategy::Fixed, WaitStrategy::Fixed) => true,
+            (
+                WaitStrategy::Backoff {
+                    start,
+                    max,
+                    factor,
+                    jitter,
+                },
+                WaitStrategy::Backoff {
+                    start: other_start,
+                    max: other_max,
+                    factor: other_factor,
+                    jitter: other_jitter,
+                },
+            ) => {
+                start == other_start
+                    && max == other_max
+                    && factor.to_bits() == other_factor.to_bits()
+                    && jitter.to_bits() == other_jitter.to_bits()
+            }
+            _ => false,
+        }
+    }
+}
+
+impl Eq for WaitStrategy {}
+
 #[derive(Clone)]
 pub struct GuardConfig {
+    /// Label of the guard in diagnostics, it does not affect behavior.
+    pub name: Option<Cow<'static, str>>,
     pub timeout: Timeout,
+    /// Sleep between state checks of a blocking wait instead of spinning.
+    pub poll_interval: Option<std::time::Duration>,
+    pub wait_strategy: WaitStrategy,
+    /// Invoked with the observed state on every state check of a wait, while the guard is
+    /// locked for reading.
+    pub on_poll: Option<PollHook>,
+    /// Waits beyond this many concurrent waiters fail immediately with `TooManyWaiters`.
+    pub max_waiters: Option<usize>,
+    pub set_policy: SetPolicy,
+    pub notify_policy: NotifyPolicy,
+    /// Once the flag is raised every wait fails with `Cancelled`. Raise it with
+    /// `request_shutdown` of a guard, so its async waits are woken to notice it.
+    pub shutdown: Option<Arc<AtomicBool>>,
+    /// Waits resolving with a value or timing out after this long are reported as slow.
+    pub slow_wait_threshold: Option<std::time::Duration>,
+    /// Invoked with the elapsed time of every slow wait.
+    pub on_slow_wait: Option<SlowWaitHook>,
+    /// Keep the last value across resets, for `wait_outcome` to serve it instead of `Timeout`.
+    pub serve_stale_on_timeout: bool,
+    /// Number of the latest state transitions kept for `recent_transitions`, they are only
+    /// recorded with the `history` feature.
+    pub history_capacity: usize,
 }
 
 impl Default for GuardConfig {
     fn default() -> Self {
+        GuardConfig::INFINITE
+    }
+}
+
+/// Config a guard of the type gets from `SyncGuard::configured`, implemented by value types
+/// with sensible defaults of their own, e.g. a timeout for a response.
+pub trait DefaultGuardConfig {
+    fn default_guard_config() -> GuardConfig {
+        GuardConfig::default()
+    }
+}
+
+impl GuardConfig {
+    /// Waits return immediately.
+    pub const INSTANT: GuardConfig = GuardConfig::with_timeout(Timeout::Instant);
+    /// Waits block until the guard is set or killed, the default.
+    pub const INFINITE: GuardConfig = GuardConfig::with_timeout(Timeout::Infinite);
+
+    /// Waits time out after `secs` seconds.
+    pub const fn timeout_secs(secs: u64) -> Self {
+        GuardConfig::with_timeout(Timeout::Duration(std::time::Duration::from_secs(secs)))
+    }
+
+    const fn with_timeout(timeout: Timeout) -> Self {
         GuardConfig {
-            timeout: Timeout::Infinite,
+            name: None,
+            timeout,
+            poll_interval: None,
+            wait_strategy: WaitStrategy::Fixed,
+            on_poll: None,
+            max_waiters: None,
+            set_policy: SetPolicy::Overwrite,
+            notify_policy: NotifyPolicy::All,
+            shutdown: None,
+            slow_wait_threshold: None,
+            on_slow_wait: None,
+            serve_stale_on_timeout: false,
+            history_capacity: 16,
+        }
+    }
+
+    pub(crate) fn is_shut_down(&self) -> bool {
+        matches!(&self.shutdown, Some(shutdown) if shutdown.load(Ordering::Acquire))
+    }
+
+    /// Hook to report a wait with, if it was slow.
+    pub(crate) fn slow_wait<R>(
+        &self,
+        result: &Result<R, GuardError>,
+        elapsed: std::time::Duration,
+    ) -> Option<SlowWaitHook> {
+        match (result, self.slow_wait_threshold) {
+            (Ok(_) | Err(GuardError::Timeout), Some(threshold)) if elapsed > threshold => {
+                #[cfg(feature = "tracing")]
+                tracing::warn!(
+                    name = self.name.as_deref(),
+                    elapsed_ms = elapsed.as_millis() as u64,
+                    "slow wait"
+                );
+                self.on_slow_wait.clone()
+            }
+            _ => None,
+        }
+    }
+
+    pub(crate) fn pause(&self) -> Pause {
+        let interval = match self.wait_strategy {
+            WaitStrategy::Fixed => self.poll_interval,
+            WaitStrategy::Backoff { start, .. } => Some(start),
+        };
+
+        Pause {
+            strategy: self.wait_strategy,
+            interval,
+            seed: RandomState::new().build_hasher().finish() | 1,
+        }
+    }
+}
+
+pub(crate) struct Pause {
+    strategy: WaitStrategy,
+    interval: Option<std::time::Duration>,
+    /// State of the xorshift generator of the jitter, never zero.
+    seed: u64,
+}
+
+impl Pause {
+    pub(crate) fn wait(&mut self) {
+        self.pause_with(primitives::sleep, None)
+    }
+
+    /// Pauses like `wait`, but for no longer than `limit`.
+    pub(crate) fn wait_within(&mut self, limit: Option<std::time::Duration>) {
+        self.pause_with(primitives::sleep, limit)
+    }
+
+    /// Pauses by parking the thread for no longer than `limit`, so an unpark ends the pause
+    /// early.
+    pub(crate) fn park_within(&mut self, limit: Option<std::time::Duration>) {
+        self.pause_with(primitives::park_timeout, limit)
+    }
+
+    /// Pause of the one of several waits paused together which is due the soonest.
+    pub(crate) fn soonest(pauses: &mut [Pause]) -> Option<&mut Pause> {
+        pauses
+            .iter_mut()
+            .min_by_key(|pause| pause.interval.unwrap_or_default())
+    }
+
+    fn pause_with(&mut self, pause: fn(std::time::Duration), limit: Option<std::time::Duration>) {
+        let Some(interval) = self.interval else {
+            return primitives::spin();
+        };
+        let jittered = self.jittered(interval);
+        pause(limit.map_or(jittered, |limit| jittered.min(limit)));
+
+        if let WaitStrategy::Backoff { max, factor, .. } = self.strategy {
+            // a factor below 1.0 (or NaN) does not shrink the pause, an overflow saturates
+            let grown = interval.as_secs_f64() * factor.max(1.0);
+            let grown = std::time::Duration::try_from_secs_f64(grown).unwrap_or(max);
+            self.interval = Some(grown.min(max));
+        }
+    }
+
+    fn jittered(&mut self, interval: std::time::Duration) -> std::time::Duration {
+        match self.strategy {
+            WaitStrategy::Backoff { jitter, .. } if jitter > 0.0 => {
+                self.seed ^= self.seed << 13;
+                self.seed ^= self.seed >> 7;
+                self.seed ^= self.seed << 17;
+                let unit = (self.seed >> 11) as f64 / (1u64 << 53) as f64;
+                interval.mul_f64(1.0 - jitter.min(1.0) * unit)
+            }
+            _ => interval,
+        }
+    }
+}
+
+/// Hooks and the shutdown flag are compared by identity.
+impl PartialEq for GuardConfig {
+    fn eq(&self, other: &Self) -> bool {
+        let GuardConfig {
+            name,
+            timeout,
+            poll_interval,
+            wait_strategy,
+            on_poll,
+            max_waiters,
+            set_policy,
+            notify_policy,
+            shutdown,
+            slow_wait_threshold,
+            on_slow_wait,
+            serve_stale_on_timeout,
+            history_capacity,
+        } = self;
+
+        *name == other.name
+            && *timeout == other.timeout
+            && *poll_interval == other.poll_interval
+            && *wait_strategy == other.wait_strategy
+            && same_arc(on_poll, &other.on_poll)
+            && *max_waiters == other.max_waiters
+            && *set_policy == other.set_policy
+            && *notify_policy == other.notify_policy
+            && same_arc(shutdown, &other.shutdown)
+            && *slow_wait_threshold == other.slow_wait_threshold
+            && same_arc(on_slow_wait, &other.on_slow_wait)
+            && *serve_stale_on_timeout == other.serve_stale_on_timeout
+            && *history_capacity == other.history_capacity
+    }
+}
+
+impl Eq for GuardConfig {}
+
+impl std::fmt::Debug for GuardConfig {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let GuardConfig {
+            name,
+            timeout,
+            poll_interval,
+            wait_strategy,
+            on_poll,
+            max_waiters,
+            set_policy,
+            notify_policy,
+            shutdown,
+            slow_wait_threshold,
+            on_slow_wait,
+            serve_stale_on_timeout,
+            history_capacity,
+        } = self;
+
+        f.debug_struct("GuardConfig")
+            .field("name", name)
+            .field("timeout", timeout)
+            .field("poll_interval", poll_interval)
+            .field("wait_strategy", wait_strategy)
+            .field("on_poll", &on_poll.as_ref().map(|_| ".."))
+            .field("max_waiters", max_waiters)
+            .field("set_policy", set_policy)
+            .field("notify_policy", notify_policy)
+            .field("shutdown", shutdown)
+            .field("slow_wait_threshold", slow_wait_threshold)
+            .field("on_slow_wait", &on_slow_wait.as_ref().map(|_| ".."))
+            .field("serve_stale_on_timeout", serve_stale_on_timeout)
+            .field("history_capacity", history_capacity)
+            .finish()
+    }
+}
+
+fn same_arc<F: ?Sized>(hook: &Option<Arc<F>>, other: &Option<Arc<F>>) -> bool {
+    match (hook, other) {
+        (Some(hook), Some(other)) => Arc::ptr_eq(hook, other),
+        (None, None) => true,
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::error::GuardError;
+    use crate::sync::SyncGuard;
+    use crate::{GuardConfig, Timeout, WaitStrategy};
+    use std::time::Duration;
+
+    #[test]
+    fn test_backoff_clamps_factor() {
+        let max = Duration::from_millis(100);
+        for factor in [-2.0, 0.5, f64::NAN, f64::INFINITY, f64::MAX] {
+            let config = GuardConfig {
+                wait_strategy: WaitStrategy::Backoff {
+                    start: Duration::from_millis(10),
+                    max,
+                    factor,
+                    jitter: 0.0,
+                },
+                ..Default::default()
+            };
+            let mut pause = config.pause();
+            for _ in 0..3 {
+                pause.pause_with(|_| {}, None);
+            }
+            let expected = if factor > 1.0 {
+                max
+            } else {
+                Duration::from_millis(10)
+            };
+            assert_eq!(pause.interval, Some(expected));
+        }
+    }
+
+    #[test]
+    fn test_backoff_jitter_spreads_pauses() {
+        let config = GuardConfig {
+            wait_strategy: WaitStrategy::Backoff {
+                start: Duration::from_millis(10),
+                max: Duration::from_millis(10),
+                factor: 1.0,
+                jitter: 0.5,
+            },
+            ..Default::default()
+        };
+        let mut pause = config.pause();
+        let pauses: Vec<_> = (0..100)
+            .map(|_| pause.jittered(Duration::from_millis(10)))
+            .collect();
+        assert!(pauses
+            .iter()
+            .all(|pause| (Duration::from_millis(5)..=Duration::from_millis(10)).contains(pause)));
+        assert!(pauses.iter().any(|other| *other != pauses[0]));
+
+        let mut other = config.pause();
+        assert_ne!(
+            pauses[..10],
+            (0..10)
+                .map(|_| other.jittered(Duration::from_millis(10)))
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn test_parse_timeout() {
+        assert_eq!("instant".parse(), Ok(Timeout::Instant));
+        assert_eq!("infinite".parse(), Ok(Timeout::Infinite));
+        assert_eq!(
+            "500ms".parse(),
+            Ok(Timeout::Duration(Duration::from_millis(500)))
+        );
+        assert_eq!(
+            Timeout::try_from("1s"),
+            Ok(Timeout::Duration(Duration::from_secs(1)))
+        );
+        assert_eq!(
+            "2m".parse(),
+            Ok(Timeout::Duration(Duration::from_secs(120)))
+        );
+        assert_eq!(
+            "1h".parse(),
+            Ok(Timeout::Duration(Duration::from_secs(3600)))
+        );
+        assert_eq!(
+            "250us".parse(),
+            Ok(Timeout::Duration(Duration::from_micros(250)))
+        );
+        assert_eq!(
+            "40ns".parse(),
+            Ok(Timeout::Duration(Duration::from_nanos(40)))
+        );
+
+        assert_eq!(
+            "at least 1s".parse(),
+            Ok(Timeout::AtLeast(Duration::from_secs(1)))
+        );
+        assert_eq!("5 attempts".parse(), Ok(Timeout::Attempts(5)));
+
+        for malformed in [
+            "",
+            "ms",
+            "10",
+            "1.5s",
+            "-1s",
+            "10 parsecs",
+            "forever",
+            "at least",
+            "at least forever",
+            "attempts",
+            "+5 attempts",
+            "1.5 attempts",
+            "99999999999 attempts",
+        ] {
+            assert!(malformed.parse::<Timeout>().is_err(), "{malformed}");
+        }
+    }
+
+    #[test]
+    fn test_min_timeout() {
+        let short = Timeout::Duration(Duration::from_millis(10));
+        let long = Timeout::Duration(Duration::from_millis(20));
+        let at_least = Timeout::AtLeast(Duration::from_millis(10));
+        let pairs = [
+            (Timeout::Instant, Timeout::Instant, Timeout::Instant),
+            (Timeout::Instant, short, Timeout::Instant),
+            (Timeout::Instant, Timeout::Infinite, Timeout::Instant),
+            (Timeout::Instant, at_least, Timeout::Instant),
+            (Timeout::Instant, Timeout::Attempts(5), Timeout::Instant),
+            (
+                Timeout::Attempts(5),
+                Timeout::Attempts(10),
+                Timeout::Attempts(5),
+            ),
+            (Timeout::Attempts(5), short, Timeout::Attempts(5)),
+            (Timeout::Attempts(5), at_least, Timeout::Attempts(5)),
+            (
+                Timeout::Attempts(5),
+                Timeout::Infinite,
+                Timeout::Attempts(5),
+            ),
+            (short, long, short),
+            (short, Timeout::Infinite, short),
+            (short, at_least, short),
+            (Timeout::Infinite, Timeout::Infinite, Timeout::Infinite),
+            (Timeout::Infinite, at_least, at_least),
+            (
+                at_least,
+                Timeout::AtLeast(Duration::from_millis(20)),
+                at_least,
+            ),
+        ];
+        for (timeout, other, min) in pairs {
+            assert_eq!(timeout.min(other), min, "{timeout} and {other}");
+            assert_eq!(other.min(timeout), min, "{other} and {timeout}");
+        }
+    }
+
+    #[test]
+    fn test_display_timeout() {
+        for timeout in [
+            Timeout::Instant,
+            Timeout::Infinite,
+            Timeout::Duration(Duration::from_millis(500)),
+            Timeout::Duration(Duration::from_micros(500)),
+            Timeout::Duration(Duration::from_nanos(1_000_500)),
+            Timeout::AtLeast(Duration::from_secs(1)),
+            Timeout::AtLeast(Duration::from_nanos(40)),
+            Timeout::Attempts(0),
+            Timeout::Attempts(5),
+        ] {
+            assert_eq!(timeout.to_string().parse(), Ok(timeout));
         }
+        assert_eq!(
+            Timeout::Duration(Duration::from_micros(500)).to_string(),
+            "500us"
+        );
+        assert_eq!(
+            Timeout::AtLeast(Duration::from_secs(1)).to_string(),
+            "at least 1000ms"
+        );
+        assert_eq!(Timeout::Attempts(5).to_string(), "5 attempts");
+    }
+
+    #[test]
+    fn test_config_presets() {
+        assert_eq!(GuardConfig::INFINITE, GuardConfig::default());
+        assert_eq!(
+            GuardConfig::timeout_secs(1).timeout,
+            Timeout::Duration(Duration::from_secs(1))
+        );
+
+        let guard = SyncGuard::<u8>::new(GuardConfig::INSTANT);
+        assert_eq!(guard.wait().unwrap_err(), GuardError::NotReady);
+
+        let guard = SyncGuard::<u8>::new(GuardConfig::timeout_secs(1));
+        let t0 = std::time::Instant::now();
+        assert_eq!(guard.wait().unwrap_err(), GuardError::Timeout);
+        assert!(t0.elapsed() >= Duration::from_secs(1));
+
+        let guard = SyncGuard::<u8>::new(GuardConfig::INFINITE);
+        let t_guard = guard.clone();
+        let _t = std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(20));
+            assert!(t_guard.set(42).is_ok());
+        });
+        assert_eq!(*guard.wait().unwrap(), 42);
+    }
+
+    #[test]
+    fn test_config_equality() {
+        let config = GuardConfig {
+            timeout: Timeout::Duration(Duration::from_millis(100)),
+            ..Default::default()
+        };
+        assert_eq!(config, config.clone());
+        assert_eq!(GuardConfig::default().timeout, Timeout::Infinite);
+        assert_ne!(config, GuardConfig::default());
+
+        let hooked = GuardConfig {
+            on_poll: Some(std::sync::Arc::new(|_| {})),
+            ..Default::default()
+        };
+        assert_eq!(hooked, hooked.clone());
+        assert_ne!(
+            hooked,
+            GuardConfig {
+                on_poll: Some(std::sync::Arc::new(|_| {})),
+                ..Default::default()
+            }
+        );
     }
 }
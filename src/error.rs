@@ -1,4 +1,4 @@
-#[derive(Debug, thiserror::Error)]
+#[derive(Clone, Debug, thiserror::Error)]
 #[cfg_attr(test, derive(PartialEq))]
 pub enum GuardError {
     #[error("Timeout error")]
@@ -7,4 +7,154 @@ pub enum GuardError {
     Killed,
     #[error("Unable to kill an already elapsed Guard")]
     UnableToKilled,
+    #[error("Guard has no value yet")]
+    NotReady,
+    #[error("Too many waiters")]
+    TooManyWaiters,
+    #[error("Guard already has a value")]
+    AlreadySet,
+    #[error("Cancelled")]
+    Cancelled,
+    #[error("Unable to persist the guard: {0}")]
+    Persistence(String),
+    #[error("Guard was changed concurrently")]
+    Conflict,
+    #[error("Guarded computation failed: {0}")]
+    Failed(String),
+    #[error("Value was consumed by another waiter")]
+    Consumed,
+    #[error("Timer of the async waits is unavailable: {0}")]
+    TimerUnavailable(String),
+}
+
+#[derive(Debug, thiserror::Error)]
+#[cfg_attr(test, derive(PartialEq))]
+pub enum ResultGuardError<E> {
+    #[error(transparent)]
+    Guard(#[from] GuardError),
+    #[error("Guarded computation failed: {0:?}")]
+    Failed(E),
+}
+
+impl GuardError {
+    pub fn is_timeout(&self) -> bool {
+        matches!(self, GuardError::Timeout)
+    }
+
+    /// Whether retrying the failed operation later may succeed.
+    pub fn is_recoverable(&self) -> bool {
+        matches!(
+            self,
+            GuardError::Timeout
+                | GuardError::NotReady
+                | GuardError::TooManyWaiters
+                | GuardError::Conflict
+                | GuardError::TimerUnavailable(_)
+        )
+    }
+
+    /// Whether the guard can never provide a value anymore.
+    pub fn is_terminal(&self) -> bool {
+        matches!(self, GuardError::Killed | GuardError::Failed(_))
+    }
+}
+
+#[cfg(feature = "tokio")]
+impl From<tokio::time::error::Elapsed> for GuardError {
+    fn from(_: tokio::time::error::Elapsed) -> Self {
+        GuardError::Timeout
+    }
+}
+
+impl From<GuardError> for std::io::Error {
+    fn from(err: GuardError) -> Self {
+        use std::io::ErrorKind;
+
+        let kind = match err {
+            GuardError::Timeout => ErrorKind::TimedOut,
+            GuardError::Killed => ErrorKind::BrokenPipe,
+            GuardError::NotReady => ErrorKind::WouldBlock,
+            GuardError::AlreadySet => ErrorKind::AlreadyExists,
+            GuardError::Cancelled => ErrorKind::Interrupted,
+            GuardError::Persistence(_) => ErrorKind::InvalidData,
+            GuardError::UnableToKilled
+            | GuardError::TooManyWaiters
+            | GuardError::Conflict
+            | GuardError::Failed(_)
+            | GuardError::Consumed
+            | GuardError::TimerUnavailable(_) => ErrorKind::Other,
+        };
+        std::io::Error::new(kind, err)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::error::GuardError;
+    use std::io::ErrorKind;
+
+    #[test]
+    fn test_is_timeout() {
+        assert!(GuardError::Timeout.is_timeout());
+        assert!(!GuardError::Killed.is_timeout());
+        assert!(!GuardError::NotReady.is_timeout());
+    }
+
+    #[test]
+    fn test_error_classification() {
+        let classes = [
+            (GuardError::Timeout, true, false),
+            (GuardError::Killed, false, true),
+            (GuardError::UnableToKilled, false, false),
+            (GuardError::NotReady, true, false),
+            (GuardError::TooManyWaiters, true, false),
+            (GuardError::AlreadySet, false, false),
+            (GuardError::Cancelled, false, false),
+            (GuardError::Persistence(String::new()), false, false),
+            (GuardError::Conflict, true, false),
+            (GuardError::TimerUnavailable(String::new()), true, false),
+            (GuardError::Failed(String::new()), false, true),
+            (GuardError::Consumed, false, false),
+        ];
+        for (err, recoverable, terminal) in classes {
+            assert_eq!(err.is_recoverable(), recoverable, "{err:?}");
+            assert_eq!(err.is_terminal(), terminal, "{err:?}");
+        }
+    }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test]
+    async fn test_from_elapsed() {
+        let pending = std::future::pending::<()>();
+        let elapsed = tokio::time::timeout(std::time::Duration::ZERO, pending)
+            .await
+            .unwrap_err();
+        assert_eq!(GuardError::from(elapsed), GuardError::Timeout);
+    }
+
+    #[test]
+    fn test_io_error_kind() {
+        let kinds = [
+            (GuardError::Timeout, ErrorKind::TimedOut),
+            (GuardError::Killed, ErrorKind::BrokenPipe),
+            (GuardError::UnableToKilled, ErrorKind::Other),
+            (GuardError::NotReady, ErrorKind::WouldBlock),
+            (GuardError::TooManyWaiters, ErrorKind::Other),
+            (GuardError::AlreadySet, ErrorKind::AlreadyExists),
+            (GuardError::Cancelled, ErrorKind::Interrupted),
+            (GuardError::Conflict, ErrorKind::Other),
+            (
+                GuardError::Persistence(String::new()),
+                ErrorKind::InvalidData,
+            ),
+            (GuardError::Failed(String::new()), ErrorKind::Other),
+            (GuardError::Consumed, ErrorKind::Other),
+        ];
+        for (err, kind) in kinds {
+            let message = err.to_string();
+            let err = std::io::Error::from(err);
+            assert_eq!(err.kind(), kind);
+            assert_eq!(err.to_string(), message);
+        }
+    }
 }
@@ -0,0 +1,561 @@
+use std::ops::{Deref, DerefMut};
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+
+use crate::error::GuardError;
+use crate::primitives::{AtomicBool, AtomicU64, AtomicUsize, Mutex, RwLock};
+#[cfg(feature = "history")]
+use crate::state::History;
+#[cfg(feature = "async")]
+use crate::state::Wakers;
+use crate::state::{Priorities, State, Stats, Tag};
+use crate::{GuardConfig, GuardState, GuardStats, NotifyPolicy, PollHook, SetPolicy, Timeout};
+
+pub(crate) type Init<T> = Box<dyn FnOnce() -> T + Send>;
+#[cfg(feature = "crossbeam")]
+type Ready<T> = Vec<crossbeam_channel::Sender<Arc<T>>>;
+
+/// State of a guard shared by all of its handles, sync and async alike, along with the config of
+/// the handle. Every transition of the state goes through here.
+pub(crate) struct Inner<T> {
+    pub(crate) value: Arc<RwLock<State<T>>>,
+    pub(crate) tag: Arc<Tag>,
+    #[cfg(feature = "history")]
+    pub(crate) history: Arc<History>,
+    pub(crate) config: Arc<RwLock<GuardConfig>>,
+    /// `on_poll` of the config, which never changes after the config is set, so polling does
+    /// not lock the config.
+    on_poll: Option<PollHook>,
+    pub(crate) generation: Arc<AtomicU64>,
+    pub(crate) waiters: Arc<AtomicUsize>,
+    pub(crate) priorities: Arc<Priorities>,
+    pub(crate) stats: Arc<Stats>,
+    /// Message of the error the guard was killed with by `kill_with_error`.
+    pub(crate) failure: Arc<Mutex<Option<String>>>,
+    /// Whether the value was handed off under `NotifyPolicy::Once`, cleared by the next set.
+    pub(crate) consumed: Arc<AtomicBool>,
+    /// Tasks awaiting the guard through its `AsyncGuard` handles.
+    #[cfg(feature = "async")]
+    pub(crate) wakers: Arc<Mutex<Wakers>>,
+    pub(crate) init: Arc<Mutex<Option<Init<T>>>>,
+    pub(crate) stale: Arc<Mutex<Option<Arc<T>>>>,
+    #[cfg(feature = "crossbeam")]
+    pub(crate) ready: Arc<Mutex<Ready<T>>>,
+}
+
+impl<T> Clone for Inner<T> {
+    fn clone(&self) -> Self {
+        Inner {
+            value: self.value.clone(),
+            tag: self.tag.clone(),
+            #[cfg(feature = "history")]
+            history: self.history.clone(),
+            config: self.config.clone(),
+            on_poll: self.on_poll.clone(),
+            generation: self.generation.clone(),
+            waiters: self.waiters.clone(),
+            priorities: self.priorities.clone(),
+            stats: self.stats.clone(),
+            failure: self.failure.clone(),
+            consumed: self.consumed.clone(),
+            #[cfg(feature = "async")]
+            wakers: self.wakers.clone(),
+            init: self.init.clone(),
+            stale: self.stale.clone(),
+            #[cfg(feature = "crossbeam")]
+            ready: self.ready.clone(),
+        }
+    }
+}
+
+impl<T> Default for Inner<T> {
+    fn default() -> Self {
+        Inner {
+            value: Arc::new(RwLock::default()),
+            tag: Arc::new(Tag::default()),
+            #[cfg(feature = "history")]
+            history: Arc::new(History::default()),
+            config: Arc::new(RwLock::default()),
+            on_poll: None,
+            generation: Arc::new(AtomicU64::default()),
+            waiters: Arc::new(AtomicUsize::default()),
+            priorities: Arc::new(Priorities::default()),
+            stats: Arc::new(Stats::default()),
+            failure: Arc::new(Mutex::default()),
+            consumed: Arc::new(AtomicBool::default()),
+            #[cfg(feature = "async")]
+            wakers: Arc::new(Mutex::default()),
+            init: Arc::new(Mutex::default()),
+            stale: Arc::new(Mutex::default()),
+            #[cfg(feature = "crossbeam")]
+            ready: Arc::new(Mutex::default()),
+        }
+    }
+}
+
+/// Marks the value of `from_fn` as being computed, the closure taken out of `Inner::init` is not
+/// run again. Dropped only when the computation unwinds, failing the waiters of the guard.
+struct Initializing<'a, T> {
+    inner: &'a Inner<T>,
+}
+
+impl<T> Drop for Initializing<'_, T> {
+    fn drop(&mut self) {
+        // a value set meanwhile is kept
+        let _ = self
+            .inner
+            .kill_as(Some(String::from("initializer of the guard panicked")));
+    }
+}
+
+impl<T> Inner<T> {
+    pub(crate) fn new(config: GuardConfig) -> Self {
+        Inner {
+            on_poll: config.on_poll.clone(),
+            config: Arc::new(RwLock::new(config)),
+            ..Default::default()
+        }
+    }
+
+    pub(crate) fn with_value(value: T, config: GuardConfig) -> Self {
+        let inner = Self::new(config);
+        inner.publish(inner.value.write().deref_mut(), Arc::new(value));
+        inner
+    }
+
+    /// Handle sharing the state, but with its own config derived from the current one with `f`.
+    pub(crate) fn map_config<F>(&self, f: F) -> Self
+    where
+        F: FnOnce(GuardConfig) -> GuardConfig,
+    {
+        let config = f(self.config.read().clone());
+        Inner {
+            on_poll: config.on_poll.clone(),
+            config: Arc::new(RwLock::new(config)),
+            ..self.clone()
+        }
+    }
+
+    /// Independent, unset state with a copy of the config.
+    pub(crate) fn clone_empty(&self) -> Self {
+        Self::new(self.config.read().clone())
+    }
+
+    pub(crate) fn ptr_eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.value, &other.value)
+    }
+
+    pub(crate) fn as_ptr(&self) -> *const RwLock<State<T>> {
+        Arc::as_ptr(&self.value)
+    }
+
+    pub(crate) fn fmt_debug(
+        &self,
+        f: &mut std::fmt::Formatter<'_>,
+        handle: &str,
+    ) -> std::fmt::Result {
+        f.debug_struct(handle)
+            .field(
+                "state",
+                &self.value.try_read().map(|state| state.guard_state()),
+            )
+            .field("config", self.config.read().deref())
+            .finish()
+    }
+
+    /// One-line summary, e.g. `SyncGuard(name=cache, state=Set, timeout=500ms, waiters=3)`. The
+    /// config is left out while it is locked for writing, so formatting never blocks.
+    pub(crate) fn fmt_display(
+        &self,
+        f: &mut std::fmt::Formatter<'_>,
+        handle: &str,
+    ) -> std::fmt::Result {
+        let config = self.config.try_read();
+        write!(f, "{handle}(")?;
+        if let Some(name) = config.as_ref().and_then(|config| config.name.as_deref()) {
+            write!(f, "name={name}, ")?;
+        }
+        write!(f, "state={:?}", self.state())?;
+        if let Some(config) = config {
+            write!(f, ", timeout={}", config.timeout)?;
+        }
+        write!(f, ", waiters={})", self.waiter_count())
+    }
+
+    /// Publishes the value of `from_fn`, unless it was computed already. The value is computed
+    /// without the lock, so a panic of `from_fn` kills the guard with `Failed` instead of leaving
+    /// it locked and unset.
+    pub(crate) fn initialize(&self) {
+        let init = self.init.lock().take();
+        if let Some(init) = init {
+            let initializing = Initializing { inner: self };
+            let value = Arc::new(init());
+            std::mem::forget(initializing);
+            let mut state = self.value.write();
+            if let State::UnSet = state.deref() {
+                self.publish(state.deref_mut(), value);
+            }
+        }
+    }
+
+    /// Stores `value` under the set policy, or hands it back along with the error the set fails
+    /// with, `None` when the value is rejected under `SetPolicy::RejectNewer`.
+    pub(crate) fn store<V>(&self, value: V) -> Result<(), (V, Option<GuardError>)>
+    where
+        V: Into<Arc<T>>,
+    {
+        let policy = self.config.read().set_policy;
+        match self.value.write().deref_mut() {
+            State::Killed => Err((value, Some(GuardError::Killed))),
+            State::Value(_) if policy == SetPolicy::RejectNewer => Err((value, None)),
+            State::Value(_) if policy == SetPolicy::Error => {
+                Err((value, Some(GuardError::AlreadySet)))
+            }
+            state => {
+                self.publish(state, value.into());
+                Ok(())
+            }
+        }
+    }
+
+    pub(crate) fn set_arc(&self, value: Arc<T>) -> Result<(), GuardError> {
+        self.store(value)
+            .or_else(|(_, err)| err.map_or(Ok(()), Err))
+    }
+
+    pub(crate) fn set_if_changed(&self, value: T) -> Result<bool, GuardError>
+    where
+        T: PartialEq,
+    {
+        let policy = self.config.read().set_policy;
+        match self.value.write().deref_mut() {
+            State::Killed => Err(GuardError::Killed),
+            State::Value(current) if **current == value => Ok(false),
+            State::Value(_) if policy == SetPolicy::RejectNewer => Ok(false),
+            State::Value(_) if policy == SetPolicy::Error => Err(GuardError::AlreadySet),
+            state => {
+                self.publish(state, Arc::new(value));
+                Ok(true)
+            }
+        }
+    }
+
+    pub(crate) fn compare_and_set(
+        &self,
+        expected_generation: u64,
+        value: T,
+    ) -> Result<u64, GuardError> {
+        let mut state = self.value.write();
+        if let State::Killed = state.deref() {
+            return Err(GuardError::Killed);
+        }
+        if self.generation() != expected_generation {
+            return Err(GuardError::Conflict);
+        }
+
+        Ok(self.publish(state.deref_mut(), Arc::new(value)))
+    }
+
+    /// Stores `value` into the locked `state`, notifying every kind of consumer, and returns the
+    /// new generation.
+    pub(crate) fn publish(&self, state: &mut State<T>, value: Arc<T>) -> u64 {
+        #[cfg(feature = "crossbeam")]
+        self.notify_ready(&value);
+        self.consumed.store(false, Ordering::Release);
+        *state = State::Value(value);
+        self.transition(state);
+        self.stats.set();
+        #[cfg(feature = "metrics-rs")]
+        crate::metrics::set(&self.config.read());
+        #[cfg(feature = "async")]
+        match self.config.read().notify_policy {
+            NotifyPolicy::All => self.wakers.lock().wake_all(),
+            NotifyPolicy::One | NotifyPolicy::Once => self.wakers.lock().wake_one(),
+        }
+        #[cfg(feature = "tracing")]
+        tracing::debug!(guard = self.as_ptr() as usize, "value set");
+        self.generation.fetch_add(1, Ordering::Release) + 1
+    }
+
+    pub(crate) fn kill_as(&self, failure: Option<String>) -> Result<(), GuardError> {
+        match self.value.write().deref_mut() {
+            State::Value(_) => Err(GuardError::UnableToKilled),
+            state => {
+                if let State::UnSet = state {
+                    *self.failure.lock() = failure;
+                }
+                self.kill_state(state);
+                Ok(())
+            }
+        }
+    }
+
+    pub(crate) fn force_kill(&self) -> Result<(), GuardError> {
+        let mut state = self.value.write();
+        if let State::Value(_) = state.deref() {
+            self.generation.fetch_add(1, Ordering::Release);
+        }
+        self.kill_state(state.deref_mut());
+        Ok(())
+    }
+
+    fn kill_state(&self, state: &mut State<T>) {
+        *state = State::Killed;
+        self.transition(state);
+        // dropping the senders disconnects the receivers, the guard is never going to be set
+        #[cfg(feature = "crossbeam")]
+        self.ready.lock().clear();
+        self.stats.kill();
+        #[cfg(feature = "metrics-rs")]
+        crate::metrics::kill(&self.config.read());
+        #[cfg(feature = "async")]
+        self.wakers.lock().wake_all();
+        #[cfg(feature = "tracing")]
+        tracing::debug!(guard = self.as_ptr() as usize, "guard killed");
+    }
+
+    pub(crate) fn reset(&self) -> Result<Option<T>, GuardError>
+    where
+        T: Clone,
+    {
+        self.reset_if(|_| true)
+    }
+
+    pub(crate) fn clear(&self) -> Result<bool, GuardError> {
+        Ok(self.unset(self.value.write().deref_mut()).is_some())
+    }
+
+    /// Clears the value only while it is still `value` at `generation`, so a value published
+    /// meanwhile is kept. Returns whether the value was cleared.
+    pub(crate) fn clear_if_current(&self, value: &Arc<T>, generation: u64) -> bool {
+        let mut state = self.value.write();
+        match state.deref() {
+            State::Value(current)
+                if Arc::ptr_eq(current, value) && self.generation() == generation =>
+            {
+                self.unset(state.deref_mut()).is_some()
+            }
+            _ => false,
+        }
+    }
+
+    pub(crate) fn reset_if<F>(&self, pred: F) -> Result<Option<T>, GuardError>
+    where
+        T: Clone,
+        F: FnOnce(&T) -> bool,
+    {
+        let mut state = self.value.write();
+        match state.deref() {
+            State::Value(val) if pred(val) => Ok(self
+                .unset(state.deref_mut())
+                .map(|val| Arc::try_unwrap(val).unwrap_or_else(|val| (*val).to_owned()))),
+            _ => Ok(None),
+        }
+    }
+
+    /// Clears the value of the locked `state`, keeping it to be served stale, and returns it.
+    /// A state without a value is left as it is.
+    fn unset(&self, state: &mut State<T>) -> Option<Arc<T>> {
+        match std::mem::take(state) {
+            State::Value(val) => {
+                self.retain_stale(&val);
+                self.transition(state);
+                self.generation.fetch_add(1, Ordering::Release);
+                self.stats.reset();
+                #[cfg(feature = "async")]
+                self.wakers.lock().wake_all();
+                Some(val)
+            }
+            other => {
+                *state = other;
+                None
+            }
+        }
+    }
+
+    pub(crate) fn modify<F>(&self, f: F) -> Result<(), GuardError>
+    where
+        T: Clone,
+        F: FnOnce(&mut T),
+    {
+        match self.value.write().deref_mut() {
+            State::Value(val) => {
+                f(Arc::make_mut(val));
+                self.generation.fetch_add(1, Ordering::Release);
+                #[cfg(feature = "async")]
+                self.wakers.lock().wake_all();
+                Ok(())
+            }
+            State::UnSet => Err(GuardError::NotReady),
+            State::Killed => Err(GuardError::Killed),
+        }
+    }
+
+    pub(crate) fn try_into_inner(self) -> Result<Option<T>, GuardError>
+    where
+        T: Clone,
+    {
+        let state = match Arc::try_unwrap(self.value) {
+            Ok(state) => state.into_inner(),
+            Err(value) => {
+                return match value.read().deref() {
+                    State::Value(val) => Ok(Some((**val).to_owned())),
+                    State::UnSet => Ok(None),
+                    State::Killed => Err(GuardError::Killed),
+                }
+            }
+        };
+
+        match state {
+            State::Value(val) => Ok(Some(
+                Arc::try_unwrap(val).unwrap_or_else(|val| (*val).to_owned()),
+            )),
+            State::UnSet => Ok(None),
+            State::Killed => Err(GuardError::Killed),
+        }
+    }
+
+    pub(crate) fn get(&self) -> Option<Arc<T>> {
+        if self.tag.load() != GuardState::Set {
+            return None;
+        }
+        match self.value.read().deref() {
+            State::Value(val) => Some(val.clone()),
+            State::UnSet | State::Killed => None,
+        }
+    }
+
+    pub(crate) fn set_timeout(&self, timeout: Timeout) {
+        self.config.write().timeout = timeout;
+    }
+
+    pub(crate) fn effective_deadline(&self) -> Option<std::time::Instant> {
+        let timeout = self.config.read().timeout;
+        timeout.deadline(std::time::Instant::now())
+    }
+
+    pub(crate) fn generation(&self) -> u64 {
+        self.generation.load(Ordering::Acquire)
+    }
+
+    pub(crate) fn state(&self) -> GuardState {
+        self.tag.load()
+    }
+
+    #[cfg(feature = "history")]
+    pub(crate) fn recent_transitions(&self) -> Vec<(std::time::Instant, GuardState)> {
+        self.history.snapshot()
+    }
+
+    #[cfg(feature = "async")]
+    pub(crate) fn register_waker(&self, waker: std::task::Waker) {
+        self.wakers.lock().register(&mut None, &waker);
+    }
+
+    pub(crate) fn waiter_count(&self) -> usize {
+        self.waiters.load(Ordering::Acquire)
+    }
+
+    pub(crate) fn handle_count(&self) -> usize {
+        Arc::strong_count(&self.value)
+    }
+
+    pub(crate) fn stats(&self) -> GuardStats {
+        self.stats.snapshot()
+    }
+
+    /// Raises the shutdown flag, if there is one, and wakes the pending async waits to notice it.
+    pub(crate) fn request_shutdown(&self) {
+        if let Some(shutdown) = &self.config.read().shutdown {
+            shutdown.store(true, Ordering::Release);
+            #[cfg(feature = "async")]
+            self.wakers.lock().wake_all();
+        }
+    }
+
+    #[cfg(feature = "crossbeam")]
+    pub(crate) fn ready_receiver(&self) -> crossbeam_channel::Receiver<Arc<T>> {
+        let (tx, rx) = crossbeam_channel::bounded(1);
+        // The state lock is held while registering, so a concurrent set cannot slip in between.
+        match self.value.read().deref() {
+            State::Value(val) => {
+                let _ = tx.send(val.clone());
+            }
+            State::UnSet => self.ready.lock().push(tx),
+            // the sender is dropped, so the receiver is disconnected
+            State::Killed => {}
+        }
+        rx
+    }
+
+    #[cfg(feature = "crossbeam")]
+    pub(crate) fn notify_ready(&self, value: &Arc<T>) {
+        for tx in self.ready.lock().drain(..) {
+            let _ = tx.try_send(value.clone());
+        }
+    }
+
+    pub(crate) fn retain_stale(&self, value: &Arc<T>) {
+        if self.config.read().serve_stale_on_timeout {
+            *self.stale.lock() = Some(value.clone());
+        }
+    }
+
+    /// Current state as seen by a wait, under `NotifyPolicy::One` the observed value is consumed.
+    pub(crate) fn observe(&self, priority: u8) -> State<T> {
+        // polling an unset guard does not contend on the lock with the producer
+        if self.tag.load() == GuardState::UnSet {
+            return self.inspect(&State::UnSet).clone();
+        }
+        let policy = self.config.read().notify_policy;
+        if policy == NotifyPolicy::All {
+            return self.inspect(self.value.read().deref()).clone();
+        }
+
+        let mut state = self.value.write();
+        match self.inspect(state.deref()) {
+            // left to be consumed by a waiter with a higher priority
+            State::Value(_) if self.priorities.outranked(priority) => State::UnSet,
+            State::Value(_) => {
+                self.generation.fetch_add(1, Ordering::Release);
+                let value = std::mem::take(state.deref_mut());
+                self.transition(state.deref());
+                if policy == NotifyPolicy::Once {
+                    self.consumed.store(true, Ordering::Release);
+                    // the other waiters fail instead of waiting for another value
+                    #[cfg(feature = "async")]
+                    self.wakers.lock().wake_all();
+                }
+                value
+            }
+            other => other.clone(),
+        }
+    }
+
+    /// Mirrors a transition of the state into the tag, and into the history when recorded.
+    pub(crate) fn transition(&self, state: &State<T>) {
+        self.tag.store(state);
+        #[cfg(feature = "history")]
+        self.history
+            .record(self.config.read().history_capacity, state.guard_state());
+    }
+
+    pub(crate) fn inspect<'a>(&self, state: &'a State<T>) -> &'a State<T> {
+        if let Some(on_poll) = &self.on_poll {
+            on_poll(&state.guard_state());
+        }
+        state
+    }
+
+    pub(crate) fn is_consumed(&self) -> bool {
+        self.consumed.load(Ordering::Acquire)
+    }
+
+    /// Error a wait on the killed guard fails with.
+    pub(crate) fn killed(&self) -> GuardError {
+        match self.failure.lock().clone() {
+            Some(reason) => GuardError::Failed(reason),
+            None => GuardError::Killed,
+        }
+    }
+}
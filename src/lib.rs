@@ -1,9 +1,28 @@
 mod config;
+mod inner;
+#[cfg(feature = "metrics-rs")]
+mod metrics;
+#[cfg(feature = "serde")]
+mod persist;
+mod primitives;
 mod state;
+#[cfg(feature = "async")]
+mod timer;
+#[cfg(feature = "tracing")]
+mod trace;
 
+pub mod any;
 #[cfg(feature = "async")]
 pub mod r#async;
+#[cfg(feature = "bytes")]
+pub mod bytes;
 pub mod error;
+#[cfg(feature = "async")]
+pub mod local;
 pub mod sync;
 
-pub use config::{GuardConfig, Timeout};
+pub use config::{
+    DefaultGuardConfig, GuardConfig, NotifyPolicy, ParseTimeoutError, PollHook, SetPolicy,
+    SlowWaitHook, Timeout, WaitStrategy,
+};
+pub use state::{GuardReadRef, GuardState, GuardStats};
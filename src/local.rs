@@ -0,0 +1,468 @@
+use std::cell::{Cell, RefCell};
+use std::future::{Future, IntoFuture};
+use std::ops::{Deref, DerefMut};
+use std::pin::Pin;
+use std::rc::Rc;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+
+use crate::error::GuardError;
+use crate::state::{State, Wakers};
+use crate::timer::{self, Alarm};
+use crate::{GuardConfig, GuardState, SetPolicy, Timeout};
+
+/// Guard for single-threaded executors, e.g. a `tokio::task::LocalSet`. The state is behind
+/// `Rc<RefCell<..>>` instead of locks, so neither the value nor the guard has to be `Send`.
+/// Some fields of the config have no effect on it:
+/// - `notify_policy`: a value is never consumed, every waiter receives it.
+/// - `serve_stale_on_timeout`: there is no `wait_outcome` to serve a stale value.
+/// - `history_capacity`: transitions are not recorded.
+///
+/// Nor are the operations counted for `stats`.
+pub struct LocalGuard<T> {
+    value: Rc<RefCell<State<T>>>,
+    config: Rc<GuardConfig>,
+    wakers: Rc<RefCell<Wakers>>,
+    waiters: Rc<Cell<usize>>,
+}
+
+impl<T> std::fmt::Debug for LocalGuard<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("LocalGuard")
+            .field("state", &self.try_state())
+            .field("config", self.config.deref())
+            .finish()
+    }
+}
+
+/// One-line summary, e.g. `LocalGuard(name=cache, state=Set, timeout=500ms, waiters=3)`. The
+/// state is left out while it is being changed, e.g. by the drop of a replaced value.
+impl<T> std::fmt::Display for LocalGuard<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "LocalGuard(")?;
+        if let Some(name) = self.config.name.as_deref() {
+            write!(f, "name={name}, ")?;
+        }
+        if let Some(state) = self.try_state() {
+            write!(f, "state={state:?}, ")?;
+        }
+        write!(
+            f,
+            "timeout={}, waiters={})",
+            self.config.timeout,
+            self.waiter_count()
+        )
+    }
+}
+
+impl<T> Clone for LocalGuard<T> {
+    fn clone(&self) -> Self {
+        LocalGuard {
+            value: self.value.clone(),
+            config: self.config.clone(),
+            wakers: self.wakers.clone(),
+            waiters: self.waiters.clone(),
+        }
+    }
+}
+
+impl<T> Default for LocalGuard<T> {
+    fn default() -> Self {
+        LocalGuard {
+            value: Rc::default(),
+            config: Rc::default(),
+            wakers: Rc::default(),
+            waiters: Rc::default(),
+        }
+    }
+}
+
+impl<T> LocalGuard<T> {
+    /// The config is shared by every clone of the guard.
+    pub fn new(config: GuardConfig) -> Self {
+        LocalGuard {
+            config: Rc::new(config),
+            ..Default::default()
+        }
+    }
+
+    pub async fn wait(&self) -> Result<Arc<T>, GuardError> {
+        self.await
+    }
+
+    pub fn set(&self, value: T) -> Result<(), GuardError> {
+        let mut state = self.value.borrow_mut();
+        match state.deref() {
+            State::Killed => return Err(GuardError::Killed),
+            State::Value(_) if self.config.set_policy == SetPolicy::RejectNewer => return Ok(()),
+            State::Value(_) if self.config.set_policy == SetPolicy::Error => {
+                return Err(GuardError::AlreadySet)
+            }
+            _ => *state = State::Value(Arc::new(value)),
+        }
+        drop(state);
+        self.wakers.borrow_mut().wake_all();
+
+        Ok(())
+    }
+
+    /// Fails with `UnableToKilled` if the guard already has a value.
+    pub fn kill(&self) -> Result<(), GuardError> {
+        let mut state = self.value.borrow_mut();
+        if let State::Value(_) = state.deref() {
+            return Err(GuardError::UnableToKilled);
+        }
+        *state = State::Killed;
+        drop(state);
+        self.wakers.borrow_mut().wake_all();
+
+        Ok(())
+    }
+
+    /// Current value without waiting, `None` when the guard is either unset or killed.
+    pub fn get(&self) -> Option<Arc<T>> {
+        match self.value.borrow().deref() {
+            State::Value(val) => Some(val.clone()),
+            State::UnSet | State::Killed => None,
+        }
+    }
+
+    pub fn is_set(&self) -> bool {
+        matches!(self.value.borrow().deref(), State::Value(_))
+    }
+
+    /// Clears the value, returning it. A killed guard stays killed.
+    pub fn reset(&self) -> Result<Option<T>, GuardError>
+    where
+        T: Clone,
+    {
+        let mut state = self.value.borrow_mut();
+        match std::mem::take(state.deref_mut()) {
+            State::Value(val) => {
+                drop(state);
+                Ok(Some(
+                    Arc::try_unwrap(val).unwrap_or_else(|val| (*val).to_owned()),
+                ))
+            }
+            other => {
+                *state = other;
+                Ok(None)
+            }
+        }
+    }
+
+    pub fn waiter_count(&self) -> usize {
+        self.waiters.get()
+    }
+
+    fn try_state(&self) -> Option<GuardState> {
+        let state = self.value.try_borrow().ok()?;
+        Some(state.guard_state())
+    }
+
+    /// Raises the shutdown flag of the config, waking the pending waits so they fail with
+    /// `Cancelled`. Waits notice a flag raised directly only once they are woken.
+    pub fn request_shutdown(&self) {
+        if let Some(shutdown) = &self.config.shutdown {
+            shutdown.store(true, Ordering::Release);
+            self.wakers.borrow_mut().wake_all();
+        }
+    }
+
+    fn inspect(&self) -> State<T> {
+        let state = self.value.borrow();
+        if let Some(on_poll) = &self.config.on_poll {
+            on_poll(&state.guard_state());
+        }
+        match state.deref() {
+            State::Value(val) => State::Value(val.clone()),
+            State::UnSet => State::UnSet,
+            State::Killed => State::Killed,
+        }
+    }
+}
+
+/// Registration of a pending wait, counted against `max_waiters`.
+struct LocalWaiter<'a> {
+    waiters: &'a Cell<usize>,
+}
+
+impl<'a> LocalWaiter<'a> {
+    fn register(waiters: &'a Cell<usize>, max_waiters: Option<usize>) -> Result<Self, GuardError> {
+        match max_waiters {
+            Some(max) if waiters.get() >= max => Err(GuardError::TooManyWaiters),
+            _ => {
+                waiters.set(waiters.get() + 1);
+                Ok(LocalWaiter { waiters })
+            }
+        }
+    }
+}
+
+impl Drop for LocalWaiter<'_> {
+    fn drop(&mut self) {
+        self.waiters.set(self.waiters.get() - 1);
+    }
+}
+
+pub struct LocalWaitFuture<'a, T> {
+    guard: &'a LocalGuard<T>,
+    waiter: Option<LocalWaiter<'a>>,
+    waker: Option<usize>,
+    t0: Option<std::time::Instant>,
+    polls: u32,
+    alarm: Alarm,
+}
+
+impl<'a, T> IntoFuture for &'a LocalGuard<T> {
+    type Output = Result<Arc<T>, GuardError>;
+    type IntoFuture = LocalWaitFuture<'a, T>;
+
+    fn into_future(self) -> Self::IntoFuture {
+        LocalWaitFuture {
+            guard: self,
+            waiter: None,
+            waker: None,
+            t0: None,
+            polls: 0,
+            alarm: Alarm::default(),
+        }
+    }
+}
+
+impl<T> Future for LocalWaitFuture<'_, T> {
+    type Output = Result<Arc<T>, GuardError>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let guard = self.guard;
+        if self.waiter.is_none() {
+            match LocalWaiter::register(&guard.waiters, guard.config.max_waiters) {
+                Ok(waiter) => self.waiter = Some(waiter),
+                Err(err) => return Poll::Ready(Err(err)),
+            }
+        }
+        let t0 = *self.t0.get_or_insert_with(std::time::Instant::now);
+
+        let poll = self.as_mut().poll_state(t0, cx);
+        if let Poll::Ready(result) = &poll {
+            self.waiter = None;
+            let elapsed = t0.elapsed();
+            if let Some(on_slow_wait) = guard.config.slow_wait(result, elapsed) {
+                on_slow_wait(elapsed);
+            }
+        }
+        poll
+    }
+}
+
+impl<T> LocalWaitFuture<'_, T> {
+    fn poll_state(
+        mut self: Pin<&mut Self>,
+        t0: std::time::Instant,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<Arc<T>, GuardError>> {
+        let guard = self.guard;
+        if guard.config.is_shut_down() {
+            return Poll::Ready(Err(GuardError::Cancelled));
+        }
+
+        let timeout = guard.config.timeout;
+        if timeout != Timeout::Instant {
+            // registered before checking the state, so a set in between is not missed
+            guard
+                .wakers
+                .borrow_mut()
+                .register(&mut self.waker, cx.waker());
+        }
+        if let Timeout::AtLeast(min) = timeout {
+            if t0.elapsed() < min {
+                if let State::Killed = guard.inspect() {
+                    return Poll::Ready(Err(GuardError::Killed));
+                }
+                return self.alarm.pending_until(Some(t0 + min), cx.waker());
+            }
+        }
+
+        match guard.inspect() {
+            State::Value(val) => return Poll::Ready(Ok(val)),
+            State::Killed => return Poll::Ready(Err(GuardError::Killed)),
+            State::UnSet => {}
+        }
+        self.polls += 1;
+        match timeout {
+            Timeout::Instant => Poll::Ready(Err(GuardError::NotReady)),
+            timeout if timeout.is_exceeded(t0, self.polls) => Poll::Ready(Err(GuardError::Timeout)),
+            timeout => {
+                let at = timer::recheck_at(timeout, t0, guard.config.poll_interval);
+                self.alarm.pending_until(at, cx.waker())
+            }
+        }
+    }
+}
+
+impl<T> Drop for LocalWaitFuture<'_, T> {
+    fn drop(&mut self) {
+        if let Some(id) = self.waker {
+            self.guard.wakers.borrow_mut().remove(id);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+    use std::rc::Rc;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use crate::error::GuardError;
+    use crate::local::LocalGuard;
+    use crate::{GuardConfig, NotifyPolicy, Timeout};
+
+    #[tokio::test]
+    async fn test_local_guard_not_send_value() {
+        let local = tokio::task::LocalSet::new();
+        local
+            .run_until(async {
+                let guard = LocalGuard::<Rc<Cell<u32>>>::default();
+                let t_guard = guard.clone();
+                let waiter = tokio::task::spawn_local(async move {
+                    let counter = t_guard.wait().await.unwrap();
+                    counter.set(counter.get() + 1);
+                });
+
+                tokio::task::yield_now().await;
+                let counter = Rc::new(Cell::new(41));
+                assert!(guard.set(counter.clone()).is_ok());
+                assert!(waiter.await.is_ok());
+                assert_eq!(counter.get(), 42);
+                assert!(guard.kill().is_err());
+            })
+            .await;
+    }
+
+    #[tokio::test]
+    async fn test_local_guard_ignores_notify_policy() {
+        let config = GuardConfig {
+            notify_policy: NotifyPolicy::One,
+            ..Default::default()
+        };
+        let guard = LocalGuard::<u8>::new(config);
+        let set = async {
+            tokio::task::yield_now().await;
+            assert!(guard.set(42).is_ok());
+        };
+
+        let (first, second, _) = tokio::join!(guard.wait(), guard.wait(), set);
+        assert_eq!(*first.unwrap(), 42);
+        assert_eq!(*second.unwrap(), 42);
+    }
+
+    #[tokio::test]
+    async fn test_local_guard_timeout() {
+        let config = GuardConfig {
+            timeout: Timeout::Duration(Duration::from_millis(10)),
+            ..Default::default()
+        };
+        let guard = LocalGuard::<Rc<u8>>::new(config);
+
+        assert!(matches!(guard.wait().await, Err(GuardError::Timeout)));
+        assert!(guard.kill().is_ok());
+        assert!(matches!(guard.wait().await, Err(GuardError::Killed)));
+        assert!(matches!(guard.set(Rc::new(1)), Err(GuardError::Killed)));
+    }
+
+    #[test]
+    fn test_local_guard_at_least() {
+        let config = GuardConfig {
+            timeout: Timeout::AtLeast(Duration::from_millis(30)),
+            ..Default::default()
+        };
+        let guard = LocalGuard::<Rc<u8>>::new(config);
+        assert!(guard.set(Rc::new(42)).is_ok());
+
+        let t0 = std::time::Instant::now();
+        assert_eq!(**futures::executor::block_on(guard.wait()).unwrap(), 42);
+        assert!(t0.elapsed() >= Duration::from_millis(30));
+    }
+
+    #[tokio::test]
+    async fn test_local_guard_max_waiters() {
+        let config = GuardConfig {
+            max_waiters: Some(1),
+            ..Default::default()
+        };
+        let guard = LocalGuard::<u8>::new(config);
+        let mut first = Box::pin(guard.wait());
+
+        tokio::select! {
+            biased;
+            _ = &mut first => panic!("guard should not be resolved"),
+            _ = tokio::task::yield_now() => {}
+        }
+        assert_eq!(guard.waiter_count(), 1);
+        assert!(matches!(
+            guard.wait().await,
+            Err(GuardError::TooManyWaiters)
+        ));
+
+        assert!(guard.set(42).is_ok());
+        assert_eq!(*first.await.unwrap(), 42);
+        assert_eq!(guard.waiter_count(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_local_guard_slow_wait() {
+        let slow = Arc::new(AtomicUsize::new(0));
+        let t_slow = slow.clone();
+        let config = GuardConfig {
+            timeout: Timeout::Duration(Duration::from_millis(20)),
+            slow_wait_threshold: Some(Duration::from_millis(10)),
+            on_slow_wait: Some(Arc::new(move |_| {
+                t_slow.fetch_add(1, Ordering::SeqCst);
+            })),
+            ..Default::default()
+        };
+        let guard = LocalGuard::<u8>::new(config);
+
+        assert!(matches!(guard.wait().await, Err(GuardError::Timeout)));
+        assert_eq!(slow.load(Ordering::SeqCst), 1);
+        assert!(guard.set(42).is_ok());
+        assert!(guard.wait().await.is_ok());
+        assert_eq!(slow.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn test_local_guard_reset() {
+        let guard = LocalGuard::<String>::default();
+        assert_eq!(guard.reset(), Ok(None));
+        assert!(guard.set(String::from("value")).is_ok());
+        assert_eq!(guard.reset(), Ok(Some(String::from("value"))));
+        assert!(!guard.is_set());
+
+        assert!(guard.kill().is_ok());
+        assert_eq!(guard.reset(), Ok(None));
+        assert!(matches!(guard.set(String::new()), Err(GuardError::Killed)));
+    }
+
+    #[test]
+    fn test_local_guard_display() {
+        let config = GuardConfig {
+            name: Some("cache".into()),
+            timeout: Timeout::Duration(Duration::from_millis(500)),
+            ..Default::default()
+        };
+        let guard = LocalGuard::<u8>::new(config);
+        assert!(guard.set(42).is_ok());
+        assert_eq!(
+            guard.to_string(),
+            "LocalGuard(name=cache, state=Set, timeout=500ms, waiters=0)"
+        );
+
+        let debug = format!("{:?}", guard);
+        assert!(debug.contains("name: Some(\"cache\")"));
+        assert!(debug.contains("Set"));
+    }
+}
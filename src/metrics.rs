@@ -0,0 +1,97 @@
+use std::time::Duration;
+
+use crate::error::GuardError;
+use crate::GuardConfig;
+
+fn guard_label(config: &GuardConfig) -> String {
+    config.name.as_deref().unwrap_or_default().to_owned()
+}
+
+pub(crate) fn set(config: &GuardConfig) {
+    ::metrics::counter!("defender_sets_total", "guard" => guard_label(config)).increment(1);
+}
+
+pub(crate) fn kill(config: &GuardConfig) {
+    ::metrics::counter!("defender_kills_total", "guard" => guard_label(config)).increment(1);
+}
+
+pub(crate) fn waiters(config: &GuardConfig, count: usize) {
+    ::metrics::gauge!("defender_waiters", "guard" => guard_label(config)).set(count as f64);
+}
+
+pub(crate) fn waited<R>(config: &GuardConfig, result: &Result<R, GuardError>, elapsed: Duration) {
+    let guard = guard_label(config);
+    if let Err(GuardError::Timeout) = result {
+        ::metrics::counter!("defender_timeouts_total", "guard" => guard.clone()).increment(1);
+    }
+    ::metrics::histogram!("defender_wait_seconds", "guard" => guard).record(elapsed.as_secs_f64());
+}
+
+#[cfg(test)]
+mod tests {
+    use metrics_util::debugging::{DebugValue, DebuggingRecorder};
+    use metrics_util::MetricKind;
+    use std::time::Duration;
+
+    use crate::sync::SyncGuard;
+    use crate::{GuardConfig, Timeout};
+
+    #[test]
+    fn test_metrics_emitted() {
+        let recorder = DebuggingRecorder::new();
+        let snapshotter = recorder.snapshotter();
+        metrics::with_local_recorder(&recorder, || {
+            let config = GuardConfig {
+                name: Some("cache".into()),
+                timeout: Timeout::Duration(Duration::from_millis(10)),
+                ..Default::default()
+            };
+            let guard = SyncGuard::<u8>::new(config);
+            assert!(guard.wait().is_err());
+            assert!(guard.set(42).is_ok());
+            assert!(guard.wait().is_ok());
+            assert!(guard.force_kill().is_ok());
+        });
+
+        let metrics: Vec<_> = snapshotter
+            .snapshot()
+            .into_vec()
+            .into_iter()
+            .map(|(key, _, _, value)| {
+                let label = key
+                    .key()
+                    .labels()
+                    .next()
+                    .map(|label| label.value().to_owned());
+                assert_eq!(label.as_deref(), Some("cache"));
+                (key.kind(), key.key().name().to_owned(), value)
+            })
+            .collect();
+        let value = |kind: MetricKind, name: &str| {
+            metrics
+                .iter()
+                .find(|metric| metric.0 == kind && metric.1 == name)
+                .map(|metric| &metric.2)
+        };
+        assert_eq!(
+            value(MetricKind::Counter, "defender_sets_total"),
+            Some(&DebugValue::Counter(1))
+        );
+        assert_eq!(
+            value(MetricKind::Counter, "defender_kills_total"),
+            Some(&DebugValue::Counter(1))
+        );
+        assert_eq!(
+            value(MetricKind::Counter, "defender_timeouts_total"),
+            Some(&DebugValue::Counter(1))
+        );
+        assert!(matches!(
+            value(MetricKind::Histogram, "defender_wait_seconds"),
+            Some(DebugValue::Histogram(waits)) if waits.len() == 2
+        ));
+        assert_eq!(
+            value(MetricKind::Gauge, "defender_waiters"),
+            Some(&DebugValue::Gauge(0.0.into()))
+        );
+    }
+}
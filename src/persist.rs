@@ -0,0 +1,117 @@
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::io::{Read, Write};
+
+use crate::error::GuardError;
+use crate::sync::SyncGuard;
+use crate::GuardConfig;
+
+/// Persisted form of a guard, unset and killed guards are stored as a bare marker, a guard killed
+/// by `kill_with_error` along with the message of the error.
+#[derive(Serialize, Deserialize)]
+enum Persisted<T> {
+    UnSet,
+    Value(T),
+    Killed,
+    Failed(String),
+}
+
+impl<T: Serialize + DeserializeOwned> SyncGuard<T> {
+    /// Serializes the current state of the guard into `w` without waiting, returning whether a
+    /// value was written.
+    pub fn save_to_writer<W: Write>(&self, w: W) -> Result<bool, GuardError> {
+        let snapshot = self.snapshot();
+        let persisted = match &snapshot {
+            Ok(snapshot) => Persisted::Value(snapshot.value().as_ref()),
+            Err(GuardError::NotReady) => Persisted::UnSet,
+            Err(GuardError::Killed) => match self.inner.failure.lock().clone() {
+                Some(reason) => Persisted::Failed(reason),
+                None => Persisted::Killed,
+            },
+            Err(err) => return Err(GuardError::Persistence(err.to_string())),
+        };
+
+        serde_json::to_writer(w, &persisted)
+            .map(|_| snapshot.is_ok())
+            .map_err(|err| GuardError::Persistence(err.to_string()))
+    }
+
+    /// Restores a guard saved with `save_to_writer`, pre-populated with the saved value.
+    pub fn load_from_reader<R: Read>(r: R, config: GuardConfig) -> Result<Self, GuardError> {
+        let persisted =
+            serde_json::from_reader(r).map_err(|err| GuardError::Persistence(err.to_string()))?;
+
+        let guard = SyncGuard::new(config);
+        match persisted {
+            Persisted::UnSet => {}
+            Persisted::Value(value) => guard.set(value)?,
+            Persisted::Killed => guard.kill()?,
+            Persisted::Failed(reason) => guard.inner.kill_as(Some(reason))?,
+        }
+
+        Ok(guard)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::error::GuardError;
+    use crate::sync::SyncGuard;
+
+    #[test]
+    fn test_save_and_load_round_trip() {
+        let mut buffer = Vec::new();
+        let guard = SyncGuard::<Vec<String>>::default();
+        assert_eq!(guard.save_to_writer(&mut buffer), Ok(false));
+        let restored =
+            SyncGuard::<Vec<String>>::load_from_reader(buffer.as_slice(), Default::default());
+        assert_eq!(
+            restored.unwrap().snapshot().err(),
+            Some(GuardError::NotReady)
+        );
+
+        buffer.clear();
+        assert!(guard
+            .set(vec![String::from("Hello"), String::from("Guard")])
+            .is_ok());
+        assert_eq!(guard.save_to_writer(&mut buffer), Ok(true));
+        let restored =
+            SyncGuard::<Vec<String>>::load_from_reader(buffer.as_slice(), Default::default());
+        assert_eq!(*restored.unwrap().wait().unwrap(), vec!["Hello", "Guard"]);
+
+        buffer.clear();
+        let guard = SyncGuard::<Vec<String>>::default();
+        assert!(guard.kill().is_ok());
+        assert_eq!(guard.save_to_writer(&mut buffer), Ok(false));
+        let restored =
+            SyncGuard::<Vec<String>>::load_from_reader(buffer.as_slice(), Default::default());
+        assert_eq!(restored.unwrap().wait().err(), Some(GuardError::Killed));
+
+        buffer.clear();
+        let guard = SyncGuard::<Vec<String>>::default();
+        assert!(guard
+            .kill_with_error(std::io::Error::other("upstream down"))
+            .is_ok());
+        assert_eq!(guard.save_to_writer(&mut buffer), Ok(false));
+        let restored =
+            SyncGuard::<Vec<String>>::load_from_reader(buffer.as_slice(), Default::default());
+        assert_eq!(
+            restored.unwrap().wait().err(),
+            Some(GuardError::Failed(String::from("upstream down")))
+        );
+
+        // the value does not have to be `Clone`
+        buffer.clear();
+        let guard = SyncGuard::<std::sync::Mutex<u8>>::default();
+        assert!(guard.set(std::sync::Mutex::new(42)).is_ok());
+        assert_eq!(guard.save_to_writer(&mut buffer), Ok(true));
+        let restored = SyncGuard::<std::sync::Mutex<u8>>::load_from_reader(
+            buffer.as_slice(),
+            Default::default(),
+        );
+        assert_eq!(*restored.unwrap().wait().unwrap().lock().unwrap(), 42);
+
+        let restored = SyncGuard::<u8>::load_from_reader(&b"garbage"[..], Default::default());
+        assert!(matches!(restored, Err(GuardError::Persistence(_))));
+    }
+}
@@ -0,0 +1,126 @@
+//! Locks, atomics and pauses the guards are built on: `parking_lot` and `std`, or their `loom`
+//! models under `cfg(loom)`, so the guards can be model checked with
+//! `RUSTFLAGS="--cfg loom" cargo test --features loom --test loom --release`.
+
+#[cfg(all(not(loom), feature = "async"))]
+pub(crate) use parking_lot::MutexGuard;
+#[cfg(not(loom))]
+pub(crate) use parking_lot::{Mutex, RwLock, RwLockReadGuard, RwLockWriteGuard};
+#[cfg(not(loom))]
+pub(crate) use std::sync::atomic::{AtomicBool, AtomicU64, AtomicU8, AtomicUsize};
+#[cfg(not(loom))]
+pub(crate) use std::thread::{park_timeout, sleep};
+
+#[cfg(loom)]
+pub(crate) use loom::sync::atomic::{AtomicBool, AtomicU64, AtomicU8, AtomicUsize};
+#[cfg(loom)]
+pub(crate) use loom::sync::{MutexGuard, RwLockReadGuard, RwLockWriteGuard};
+
+/// Spawns a detached thread named `name`.
+#[cfg(feature = "async")]
+pub(crate) fn spawn<F: FnOnce() + Send + 'static>(name: &str, f: F) -> std::io::Result<()> {
+    #[cfg(loom)]
+    use loom::thread::Builder;
+    #[cfg(not(loom))]
+    use std::thread::Builder;
+
+    Builder::new().name(name.to_owned()).spawn(f).map(drop)
+}
+
+/// Condition variable waited on with the lock guard passed by value, the API both
+/// `parking_lot::Condvar` and `loom::sync::Condvar` can provide.
+#[cfg(feature = "async")]
+#[derive(Default)]
+pub(crate) struct Condvar {
+    #[cfg(not(loom))]
+    condvar: parking_lot::Condvar,
+    #[cfg(loom)]
+    condvar: loom::sync::Condvar,
+}
+
+#[cfg(feature = "async")]
+impl Condvar {
+    /// Waits for a notification or `timeout`, returning whether the wait timed out.
+    #[cfg(not(loom))]
+    pub(crate) fn wait_for<'a, T>(
+        &self,
+        mut guard: MutexGuard<'a, T>,
+        timeout: std::time::Duration,
+    ) -> (MutexGuard<'a, T>, bool) {
+        let timed_out = self.condvar.wait_for(&mut guard, timeout).timed_out();
+        (guard, timed_out)
+    }
+
+    #[cfg(loom)]
+    pub(crate) fn wait_for<'a, T>(
+        &self,
+        guard: MutexGuard<'a, T>,
+        timeout: std::time::Duration,
+    ) -> (MutexGuard<'a, T>, bool) {
+        let (guard, result) = self.condvar.wait_timeout(guard, timeout).unwrap();
+        (guard, result.timed_out())
+    }
+
+    pub(crate) fn notify_one(&self) {
+        self.condvar.notify_one();
+    }
+}
+
+/// Hint for a wait checking the guard again without pausing.
+pub(crate) fn spin() {
+    #[cfg(not(loom))]
+    std::hint::spin_loop();
+    #[cfg(loom)]
+    loom::thread::yield_now();
+}
+
+/// Time is not modelled, a pause yields to the other threads of the model instead.
+#[cfg(loom)]
+pub(crate) fn sleep(_: std::time::Duration) {
+    loom::thread::yield_now();
+}
+
+#[cfg(loom)]
+pub(crate) fn park_timeout(_: std::time::Duration) {
+    loom::thread::yield_now();
+}
+
+/// `loom::sync::Mutex` behind the non-poisoning API of `parking_lot::Mutex`.
+#[cfg(loom)]
+#[derive(Default)]
+pub(crate) struct Mutex<T>(loom::sync::Mutex<T>);
+
+#[cfg(loom)]
+impl<T> Mutex<T> {
+    pub(crate) fn lock(&self) -> MutexGuard<'_, T> {
+        self.0.lock().unwrap()
+    }
+}
+
+/// `loom::sync::RwLock` behind the non-poisoning API of `parking_lot::RwLock`.
+#[cfg(loom)]
+#[derive(Default)]
+pub(crate) struct RwLock<T>(loom::sync::RwLock<T>);
+
+#[cfg(loom)]
+impl<T> RwLock<T> {
+    pub(crate) fn new(value: T) -> Self {
+        RwLock(loom::sync::RwLock::new(value))
+    }
+
+    pub(crate) fn read(&self) -> RwLockReadGuard<'_, T> {
+        self.0.read().unwrap()
+    }
+
+    pub(crate) fn try_read(&self) -> Option<RwLockReadGuard<'_, T>> {
+        self.0.try_read().ok()
+    }
+
+    pub(crate) fn write(&self) -> RwLockWriteGuard<'_, T> {
+        self.0.write().unwrap()
+    }
+
+    pub(crate) fn into_inner(self) -> T {
+        self.0.into_inner().unwrap()
+    }
+}
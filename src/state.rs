@@ -1,13 +1,308 @@
+use std::collections::BTreeMap;
+#[cfg(feature = "async")]
+use std::collections::HashMap;
+#[cfg(feature = "history")]
+use std::collections::VecDeque;
+use std::ops::Deref;
+use std::sync::atomic::Ordering;
 use std::sync::Arc;
+#[cfg(feature = "async")]
+use std::task::Waker;
 
+use crate::error::GuardError;
+use crate::primitives::{AtomicU64, AtomicU8, AtomicUsize, Mutex, RwLockReadGuard};
+
+#[derive(Default)]
 pub(crate) enum State<T> {
+    #[default]
     UnSet,
     Value(Arc<T>),
     Killed,
 }
 
-impl<T> Default for State<T> {
-    fn default() -> Self {
-        State::UnSet
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GuardState {
+    UnSet,
+    Set,
+    Killed,
+}
+
+impl<T> Clone for State<T> {
+    fn clone(&self) -> Self {
+        match self {
+            State::UnSet => State::UnSet,
+            State::Value(val) => State::Value(val.clone()),
+            State::Killed => State::Killed,
+        }
+    }
+}
+
+impl<T> State<T> {
+    pub(crate) fn guard_state(&self) -> GuardState {
+        match self {
+            State::UnSet => GuardState::UnSet,
+            State::Value(_) => GuardState::Set,
+            State::Killed => GuardState::Killed,
+        }
+    }
+}
+
+/// Borrowed value of a guard, holding its read lock until dropped. A value handed to a single
+/// waiter under `NotifyPolicy::One` or `Once` is no longer in the guard, so it is held instead.
+pub struct GuardReadRef<'a, T> {
+    value: ReadRef<'a, T>,
+}
+
+enum ReadRef<'a, T> {
+    /// Always a `State::Value`, checked on creation.
+    Locked(RwLockReadGuard<'a, State<T>>),
+    Taken(Arc<T>),
+}
+
+impl<'a, T> GuardReadRef<'a, T> {
+    #[cfg(feature = "async")]
+    pub(crate) fn try_new(state: RwLockReadGuard<'a, State<T>>) -> Option<Self> {
+        match state.deref() {
+            State::Value(_) => Some(GuardReadRef {
+                value: ReadRef::Locked(state),
+            }),
+            _ => None,
+        }
+    }
+
+    /// Borrows `value` under the lock of `state` if the guard still holds it.
+    pub(crate) fn of(state: RwLockReadGuard<'a, State<T>>, value: Arc<T>) -> Self {
+        let value = match state.deref() {
+            State::Value(current) if Arc::ptr_eq(current, &value) => ReadRef::Locked(state),
+            _ => ReadRef::Taken(value),
+        };
+        GuardReadRef { value }
+    }
+}
+
+impl<T> Deref for GuardReadRef<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        match &self.value {
+            ReadRef::Locked(state) => match state.deref() {
+                State::Value(val) => val,
+                State::UnSet | State::Killed => unreachable!("read ref of a guard without a value"),
+            },
+            ReadRef::Taken(val) => val,
+        }
+    }
+}
+
+/// Counters of the operations a guard went through.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct GuardStats {
+    pub sets: u64,
+    pub kills: u64,
+    pub resets: u64,
+    /// Waits failed with `Timeout`.
+    pub timeouts: u64,
+    /// Waits resolved with a value.
+    pub resolved: u64,
+    /// Highest number of concurrent waiters.
+    pub peak_waiters: usize,
+}
+
+/// Lock-free mirror of the variant of a `State`, only stored under the write lock of the state.
+#[derive(Default)]
+pub(crate) struct Tag(AtomicU8);
+
+impl Tag {
+    pub(crate) fn store<T>(&self, state: &State<T>) {
+        self.0.store(state.guard_state() as u8, Ordering::Release);
+    }
+
+    pub(crate) fn load(&self) -> GuardState {
+        match self.0.load(Ordering::Acquire) {
+            1 => GuardState::Set,
+            2 => GuardState::Killed,
+            _ => GuardState::UnSet,
+        }
+    }
+}
+
+#[derive(Default)]
+pub(crate) struct Stats {
+    sets: AtomicU64,
+    kills: AtomicU64,
+    resets: AtomicU64,
+    timeouts: AtomicU64,
+    resolved: AtomicU64,
+    peak_waiters: AtomicUsize,
+}
+
+impl Stats {
+    pub(crate) fn set(&self) {
+        self.sets.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn kill(&self) {
+        self.kills.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn reset(&self) {
+        self.resets.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn waiting(&self, waiters: usize) {
+        self.peak_waiters.fetch_max(waiters, Ordering::Relaxed);
+    }
+
+    pub(crate) fn waited<R>(&self, result: &Result<R, GuardError>) {
+        let counter = match result {
+            Ok(_) => &self.resolved,
+            Err(GuardError::Timeout) => &self.timeouts,
+            Err(_) => return,
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn snapshot(&self) -> GuardStats {
+        GuardStats {
+            sets: self.sets.load(Ordering::Relaxed),
+            kills: self.kills.load(Ordering::Relaxed),
+            resets: self.resets.load(Ordering::Relaxed),
+            timeouts: self.timeouts.load(Ordering::Relaxed),
+            resolved: self.resolved.load(Ordering::Relaxed),
+            peak_waiters: self.peak_waiters.load(Ordering::Relaxed),
+        }
+    }
+}
+
+pub(crate) struct Waiter<'a> {
+    waiters: &'a AtomicUsize,
+}
+
+impl<'a> Waiter<'a> {
+    pub(crate) fn register(
+        waiters: &'a AtomicUsize,
+        max_waiters: Option<usize>,
+    ) -> Result<Self, GuardError> {
+        waiters
+            .fetch_update(
+                Ordering::AcqRel,
+                Ordering::Acquire,
+                |count| match max_waiters {
+                    Some(max) if count >= max => None,
+                    _ => Some(count + 1),
+                },
+            )
+            .map_err(|_| GuardError::TooManyWaiters)?;
+
+        Ok(Waiter { waiters })
+    }
+}
+
+impl Drop for Waiter<'_> {
+    fn drop(&mut self) {
+        self.waiters.fetch_sub(1, Ordering::AcqRel);
+    }
+}
+
+/// Latest state transitions of a guard, oldest first.
+#[cfg(feature = "history")]
+#[derive(Default)]
+pub(crate) struct History(Mutex<VecDeque<(std::time::Instant, GuardState)>>);
+
+#[cfg(feature = "history")]
+impl History {
+    pub(crate) fn record(&self, capacity: usize, state: GuardState) {
+        let mut history = self.0.lock();
+        history.push_back((std::time::Instant::now(), state));
+        while history.len() > capacity {
+            history.pop_front();
+        }
+    }
+
+    pub(crate) fn snapshot(&self) -> Vec<(std::time::Instant, GuardState)> {
+        self.0.lock().iter().copied().collect()
+    }
+}
+
+/// Number of waiters blocked at each priority.
+#[derive(Default)]
+pub(crate) struct Priorities(Mutex<BTreeMap<u8, usize>>);
+
+impl Priorities {
+    pub(crate) fn register(&self, priority: u8) -> Ticket<'_> {
+        *self.0.lock().entry(priority).or_default() += 1;
+        Ticket {
+            priorities: self,
+            priority,
+        }
+    }
+
+    /// Whether a waiter with a higher priority than `priority` is blocked.
+    pub(crate) fn outranked(&self, priority: u8) -> bool {
+        self.0
+            .lock()
+            .last_key_value()
+            .is_some_and(|(top, _)| *top > priority)
+    }
+}
+
+pub(crate) struct Ticket<'a> {
+    priorities: &'a Priorities,
+    priority: u8,
+}
+
+impl Drop for Ticket<'_> {
+    fn drop(&mut self) {
+        let mut priorities = self.priorities.0.lock();
+        if let Some(count) = priorities.get_mut(&self.priority) {
+            *count -= 1;
+            if *count == 0 {
+                priorities.remove(&self.priority);
+            }
+        }
+    }
+}
+
+#[cfg(feature = "async")]
+#[derive(Default)]
+pub(crate) struct Wakers {
+    next_id: usize,
+    wakers: HashMap<usize, Waker>,
+}
+
+#[cfg(feature = "async")]
+impl Wakers {
+    pub(crate) fn register(&mut self, id: &mut Option<usize>, waker: &Waker) {
+        let id = *id.get_or_insert_with(|| {
+            self.next_id += 1;
+            self.next_id
+        });
+
+        match self.wakers.get_mut(&id) {
+            Some(registered) if registered.will_wake(waker) => {}
+            Some(registered) => registered.clone_from(waker),
+            None => {
+                self.wakers.insert(id, waker.clone());
+            }
+        }
+    }
+
+    /// Returns whether the waker was still registered, i.e. it has not been woken.
+    pub(crate) fn remove(&mut self, id: usize) -> bool {
+        self.wakers.remove(&id).is_some()
+    }
+
+    pub(crate) fn wake_one(&mut self) {
+        if let Some(&id) = self.wakers.keys().next() {
+            if let Some(waker) = self.wakers.remove(&id) {
+                waker.wake();
+            }
+        }
+    }
+
+    pub(crate) fn wake_all(&mut self) {
+        for (_, waker) in self.wakers.drain() {
+            waker.wake();
+        }
     }
 }
@@ -1,111 +1,930 @@
-use parking_lot::RwLock;
 use std::ops::{Deref, DerefMut};
-use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Weak};
 
-use crate::error::GuardError;
-use crate::state::State;
-use crate::{GuardConfig, Timeout};
+use crate::config::Pause;
+use crate::error::{GuardError, ResultGuardError};
+use crate::inner::Inner;
+use crate::primitives::{RwLock, RwLockWriteGuard};
+use crate::state::{GuardReadRef, State, Waiter};
+use crate::{DefaultGuardConfig, GuardConfig, GuardState, GuardStats, Timeout};
 
-pub struct SyncGuard<T: Clone> {
-    value: Arc<RwLock<State<T>>>,
-    config: GuardConfig,
+pub struct SyncGuard<T> {
+    pub(crate) inner: Inner<T>,
 }
 
-impl<T: Clone> Clone for SyncGuard<T> {
+impl<T> Clone for SyncGuard<T> {
     fn clone(&self) -> Self {
         SyncGuard {
-            value: self.value.clone(),
-            config: self.config.clone(),
+            inner: self.inner.clone(),
         }
     }
 }
 
-impl<T: Clone> Default for SyncGuard<T> {
+/// Guards are equal when they share the same state (i.e. one is a clone of the other), the
+/// guarded values are not compared.
+impl<T> PartialEq for SyncGuard<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.inner.ptr_eq(&other.inner)
+    }
+}
+
+impl<T> Eq for SyncGuard<T> {}
+
+impl<T> std::hash::Hash for SyncGuard<T> {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.inner.as_ptr().hash(state)
+    }
+}
+
+impl<T> std::fmt::Debug for SyncGuard<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.inner.fmt_debug(f, "SyncGuard")
+    }
+}
+
+/// One-line summary, e.g. `SyncGuard(name=cache, state=Set, timeout=500ms, waiters=3)`. The
+/// config is left out while it is locked for writing, so formatting never blocks.
+impl<T> std::fmt::Display for SyncGuard<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.inner.fmt_display(f, "SyncGuard")
+    }
+}
+
+impl<T> Default for SyncGuard<T> {
     fn default() -> Self {
         SyncGuard {
-            value: Arc::new(RwLock::default()),
-            config: GuardConfig::default(),
+            inner: Inner::default(),
         }
     }
 }
 
-impl<T: Clone> SyncGuard<T> {
+impl<T> SyncGuard<T> {
+    /// The config is shared by every clone of the guard.
     pub fn new(config: GuardConfig) -> Self {
         SyncGuard {
-            config,
-            ..Default::default()
+            inner: Inner::new(config),
         }
     }
 
+    /// Creates a guard with the config of `T`, see `DefaultGuardConfig`.
+    pub fn configured() -> Self
+    where
+        T: DefaultGuardConfig,
+    {
+        Self::new(T::default_guard_config())
+    }
+
+    /// Creates a guard which computes its value lazily with `f` on the first `wait`.
+    /// Concurrent waiters block until the value is published, `f` runs exactly once.
+    pub fn from_fn<F>(f: F, config: GuardConfig) -> Self
+    where
+        F: FnOnce() -> T + Send + 'static,
+    {
+        let inner = Inner::new(config);
+        *inner.init.lock() = Some(Box::new(f));
+        SyncGuard { inner }
+    }
+
+    /// Moves the guard behind an `Arc`, so it is shared by reference instead of being cloned.
+    pub fn shared(self) -> Arc<Self> {
+        Arc::new(self)
+    }
+
     pub fn wait(&self) -> Result<Arc<T>, GuardError> {
-        match self.config.timeout {
-            Timeout::Instant => match self.value.read().deref() {
-                State::Value(val) => Ok(val.clone()),
-                State::UnSet => Err(GuardError::Timeout),
-                State::Killed => Err(GuardError::Killed),
+        self.wait_with(0, &Cancel::SHUTDOWN, Arc::clone)
+    }
+
+    /// Waits like `wait`, but under `NotifyPolicy::One` a value is handed to the waiter with the
+    /// highest priority first. `wait` waits with the lowest priority, 0.
+    pub fn wait_with_priority(&self, priority: u8) -> Result<Arc<T>, GuardError> {
+        self.wait_with(priority, &Cancel::SHUTDOWN, Arc::clone)
+    }
+
+    /// Waits for the value and copies it out, without cloning the `Arc` holding it.
+    pub fn get_copy(&self) -> Result<T, GuardError>
+    where
+        T: Copy,
+    {
+        self.wait_with(0, &Cancel::SHUTDOWN, |val| **val)
+    }
+
+    fn wait_with<R, F>(&self, priority: u8, cancel: &Cancel<'_>, f: F) -> Result<R, GuardError>
+    where
+        F: Fn(&Arc<T>) -> R,
+    {
+        let inner = &self.inner;
+        #[cfg(feature = "tracing")]
+        let span = crate::trace::wait_span(inner.as_ptr(), &inner.config.read());
+        let t0 = std::time::Instant::now();
+
+        let max_waiters = inner.config.read().max_waiters;
+        let _ticket = inner.priorities.register(priority);
+        let result = Waiter::register(&inner.waiters, max_waiters).and_then(|_waiter| {
+            inner.stats.waiting(inner.waiter_count());
+            #[cfg(feature = "metrics-rs")]
+            crate::metrics::waiters(&inner.config.read(), inner.waiter_count());
+            inner.initialize();
+            self.wait_for_state(priority, cancel, f)
+        });
+        inner.stats.waited(&result);
+        let elapsed = t0.elapsed();
+        #[cfg(feature = "metrics-rs")]
+        {
+            let config = inner.config.read();
+            crate::metrics::waited(&config, &result, elapsed);
+            crate::metrics::waiters(&config, inner.waiter_count());
+        }
+        let slow_wait = inner.config.read().slow_wait(&result, elapsed);
+        if let Some(on_slow_wait) = slow_wait {
+            on_slow_wait(elapsed);
+        }
+
+        #[cfg(feature = "tracing")]
+        crate::trace::record_wait(&span, &result, t0);
+
+        result
+    }
+
+    /// Blocks up to `timeout` for the value, then borrows it holding the read lock of the guard
+    /// until the returned reference is dropped. The lock is not held while blocking.
+    pub fn wait_ref_timeout(
+        &self,
+        timeout: std::time::Duration,
+    ) -> Result<GuardReadRef<'_, T>, GuardError> {
+        let value = self.wait_with(0, &Cancel::within(timeout), Arc::clone)?;
+        Ok(GuardReadRef::of(self.inner.value.read(), value))
+    }
+
+    /// Blocks at most `max` for the value, a missed value is `None` rather than `Timeout`.
+    pub fn try_wait_for(&self, max: std::time::Duration) -> Result<Option<Arc<T>>, GuardError> {
+        match self.wait_with(0, &Cancel::within(max), Arc::clone) {
+            Ok(value) => Ok(Some(value)),
+            Err(GuardError::Timeout) => Ok(None),
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Blocks until the guard is killed, failing with `AlreadySet` if it gets a value instead.
+    /// Resolves to the message of the error the guard was killed with, if there was one.
+    pub fn wait_for_kill(&self) -> Result<Option<String>, GuardError> {
+        let inner = &self.inner;
+        let mut pause = inner.config.read().pause();
+        let t0 = std::time::Instant::now();
+        let mut polls = 0;
+        loop {
+            if inner.config.read().is_shut_down() {
+                return Err(GuardError::Cancelled);
+            }
+            match inner.inspect(inner.value.read().deref()) {
+                State::Killed => return Ok(inner.failure.lock().clone()),
+                State::Value(_) => return Err(GuardError::AlreadySet),
+                State::UnSet => {}
+            }
+            polls += 1;
+            let timeout = inner.config.read().timeout;
+            match timeout {
+                Timeout::Instant => return Err(GuardError::NotReady),
+                timeout if timeout.is_exceeded(t0, polls) => return Err(GuardError::Timeout),
+                _ => pause.wait(),
+            }
+        }
+    }
+
+    /// Waits for the value like `wait`, failing with `Cancelled` once `interrupt` is signalled.
+    /// Pauses between state checks park the thread, so the signal ends them early.
+    pub fn wait_interruptible(&self, interrupt: &InterruptHandle) -> Result<Arc<T>, GuardError> {
+        self.wait_cancellable(|| interrupt.is_signalled(), Pause::park_within)
+    }
+
+    /// Waits for the value like `wait`, failing with `Cancelled` once `should_cancel` returns
+    /// true. It is evaluated before every state check, so cancellation is noticed within a poll
+    /// interval.
+    pub fn wait_until_cancel<F>(&self, should_cancel: F) -> Result<Arc<T>, GuardError>
+    where
+        F: Fn() -> bool,
+    {
+        self.wait_cancellable(should_cancel, Pause::wait_within)
+    }
+
+    fn wait_cancellable<F>(
+        &self,
+        should_cancel: F,
+        pause: fn(&mut Pause, Option<std::time::Duration>),
+    ) -> Result<Arc<T>, GuardError>
+    where
+        F: Fn() -> bool,
+    {
+        let cancel = Cancel {
+            should_cancel: &should_cancel,
+            pause,
+            timeout: None,
+        };
+        self.wait_with(0, &cancel, Arc::clone)
+    }
+
+    /// Waits for the value like `wait`, but resolves a kill or a timeout as an outcome instead
+    /// of an error. With `serve_stale_on_timeout` a timed out wait resolves to the last value
+    /// the guard held before being reset, if there is one.
+    pub fn wait_outcome(&self) -> Result<WaitOutcome<T>, GuardError> {
+        match self.wait() {
+            Ok(value) => Ok(WaitOutcome::Value(value)),
+            Err(GuardError::Killed | GuardError::Failed(_)) => Ok(WaitOutcome::Killed),
+            Err(GuardError::Timeout) => {
+                let serve_stale = self.inner.config.read().serve_stale_on_timeout;
+                let stale = serve_stale
+                    .then(|| self.inner.stale.lock().clone())
+                    .flatten();
+                Ok(stale.map_or(WaitOutcome::TimedOut, WaitOutcome::Stale))
+            }
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Waits for the value and returns it along with the time spent blocking.
+    pub fn wait_timed(&self) -> Result<(Arc<T>, std::time::Duration), GuardError> {
+        let t0 = std::time::Instant::now();
+        let value = self.wait()?;
+
+        Ok((value, t0.elapsed()))
+    }
+
+    /// Waits for the value, converting a failed wait into the error of the caller with `f`.
+    pub fn wait_map_err<E, F>(&self, f: F) -> Result<Arc<T>, E>
+    where
+        F: FnOnce(GuardError) -> E,
+    {
+        self.wait().map_err(f)
+    }
+
+    fn wait_for_state<R, F>(&self, priority: u8, cancel: &Cancel<'_>, f: F) -> Result<R, GuardError>
+    where
+        F: Fn(&Arc<T>) -> R,
+    {
+        let inner = &self.inner;
+        let (mut pause, timeout) = {
+            let config = inner.config.read();
+            (config.pause(), cancel.timeout.unwrap_or(config.timeout))
+        };
+        if let Timeout::AtLeast(min) = timeout {
+            self.hold(min, cancel, &mut pause)?;
+        }
+        match timeout {
+            _ if cancel.is_cancelled(&inner.config) => Err(GuardError::Cancelled),
+            Timeout::Instant => match inner.observe(priority) {
+                State::Value(val) => Ok(f(&val)),
+                State::UnSet if inner.is_consumed() => Err(GuardError::Consumed),
+                State::UnSet => Err(GuardError::NotReady),
+                State::Killed => Err(inner.killed()),
             },
-            Timeout::Infinite => loop {
-                match self.value.read().deref() {
-                    State::Value(val) => break Ok(val.clone()),
-                    State::UnSet => continue,
-                    State::Killed => break Err(GuardError::Killed),
+            Timeout::Infinite | Timeout::AtLeast(_) => loop {
+                if cancel.is_cancelled(&inner.config) {
+                    break Err(GuardError::Cancelled);
                 }
+                match inner.observe(priority) {
+                    State::Value(val) => break Ok(f(&val)),
+                    State::UnSet if inner.is_consumed() => break Err(GuardError::Consumed),
+                    State::UnSet => {}
+                    State::Killed => break Err(inner.killed()),
+                }
+                (cancel.pause)(&mut pause, None);
             },
-            Timeout::Duration(timeout) => {
+            Timeout::Duration(_) | Timeout::Attempts(_) => {
                 let t0 = std::time::Instant::now();
-                while t0.elapsed() <= timeout {
-                    match self.value.read().deref() {
-                        State::Value(val) => return Ok(val.clone()),
-                        State::UnSet => continue,
-                        State::Killed => return Err(GuardError::Killed),
+                let mut polls = 0;
+                loop {
+                    if cancel.is_cancelled(&inner.config) {
+                        return Err(GuardError::Cancelled);
+                    }
+                    match inner.observe(priority) {
+                        State::Value(val) => return Ok(f(&val)),
+                        State::UnSet if inner.is_consumed() => return Err(GuardError::Consumed),
+                        State::UnSet => {}
+                        State::Killed => return Err(inner.killed()),
                     }
+                    polls += 1;
+                    if timeout.is_exceeded(t0, polls) {
+                        return Err(GuardError::Timeout);
+                    }
+                    (cancel.pause)(&mut pause, timeout.remaining(t0));
                 }
-
-                Err(GuardError::Timeout)
             }
         }
     }
 
-    pub fn set(&mut self, value: T) -> Result<(), GuardError> {
-        match self.value.write().deref_mut() {
-            State::Killed => Err(GuardError::Killed),
-            state => {
-                *state = State::Value(Arc::new(value));
-                Ok(())
+    /// Blocks for `min`, unless the guard is killed or the wait is cancelled meanwhile.
+    fn hold(
+        &self,
+        min: std::time::Duration,
+        cancel: &Cancel<'_>,
+        pause: &mut Pause,
+    ) -> Result<(), GuardError> {
+        let inner = &self.inner;
+        let t0 = std::time::Instant::now();
+        while t0.elapsed() < min {
+            if cancel.is_cancelled(&inner.config) {
+                return Err(GuardError::Cancelled);
             }
+            if let State::Killed = inner.inspect(inner.value.read().deref()) {
+                return Err(inner.killed());
+            }
+            (cancel.pause)(pause, min.checked_sub(t0.elapsed()));
+        }
+
+        Ok(())
+    }
+
+    pub fn set(&self, value: T) -> Result<(), GuardError> {
+        self.set_arc(Arc::new(value))
+    }
+
+    /// Sets a value which is already behind an `Arc`, without moving it into a new one.
+    pub fn set_arc(&self, value: Arc<T>) -> Result<(), GuardError> {
+        self.inner.set_arc(value)
+    }
+
+    /// Sets the value like `set`, but hands it back whenever it is not stored: the guard is
+    /// killed, or it already has a value under `SetPolicy::RejectNewer` or `SetPolicy::Error`.
+    pub fn set_or_reject(&self, value: T) -> Result<(), T> {
+        self.inner.store(value).map_err(|(value, _)| value)
+    }
+
+    /// Sets the value unless it equals the current one, returning whether it was stored, so an
+    /// unchanged value neither advances the generation nor notifies anyone.
+    pub fn set_if_changed(&self, value: T) -> Result<bool, GuardError>
+    where
+        T: PartialEq,
+    {
+        self.inner.set_if_changed(value)
+    }
+
+    /// Sets the value only if the guard is still at `expected_generation`, returning the new
+    /// generation, or fails with `Conflict` when another transition happened in between.
+    pub fn compare_and_set(&self, expected_generation: u64, value: T) -> Result<u64, GuardError> {
+        self.inner.compare_and_set(expected_generation, value)
+    }
+
+    /// Fails with `UnableToKilled` if the guard already has a value, see `force_kill`.
+    pub fn kill(&self) -> Result<(), GuardError> {
+        self.inner.kill_as(None)
+    }
+
+    /// Kills the guard like `kill`, waiters fail with `Failed` carrying the message of `err`.
+    /// A guard which is already killed keeps the reason it was killed with first.
+    pub fn kill_with_error<E>(&self, err: E) -> Result<(), GuardError>
+    where
+        E: std::error::Error,
+    {
+        self.inner.kill_as(Some(err.to_string()))
+    }
+
+    /// Stores the value of a successful computation, or kills the guard with its error, so a
+    /// single call handles both outcomes of a fallible producer.
+    pub fn set_result<E>(&self, result: Result<T, E>) -> Result<(), GuardError>
+    where
+        E: std::error::Error,
+    {
+        match result {
+            Ok(value) => self.set(value),
+            Err(err) => self.kill_with_error(err),
         }
     }
 
-    pub fn kill(&mut self) -> Result<(), GuardError> {
-        match self.value.write().deref_mut() {
-            State::Value(_) => Err(GuardError::UnableToKilled),
-            state => {
+    /// Kills the guard whatever its state, dropping the value it holds, unlike `kill`.
+    pub fn force_kill(&self) -> Result<(), GuardError> {
+        self.inner.force_kill()
+    }
+
+    pub fn reset(&self) -> Result<Option<T>, GuardError>
+    where
+        T: Clone,
+    {
+        self.inner.reset()
+    }
+
+    /// Resets the guard like `reset` without cloning the value out, returning whether there was
+    /// a value to clear.
+    pub fn clear(&self) -> Result<bool, GuardError> {
+        self.inner.clear()
+    }
+
+    /// Takes the value out, leaving the guard locked until the returned token is dropped, so a
+    /// replacement can be published with `ResetToken::set` without an observable unset gap.
+    /// Dropping the token without setting leaves the guard unset.
+    pub fn take(&self) -> Result<Option<(T, ResetToken<'_, T>)>, GuardError>
+    where
+        T: Clone,
+    {
+        let inner = &self.inner;
+        let mut state = inner.value.write();
+
+        match std::mem::take(state.deref_mut()) {
+            State::UnSet => Ok(None),
+            State::Killed => {
                 *state = State::Killed;
-                Ok(())
+                Err(GuardError::Killed)
+            }
+            State::Value(val) => {
+                inner.generation.fetch_add(1, Ordering::Release);
+                inner.stats.reset();
+                inner.retain_stale(&val);
+                let value = Arc::try_unwrap(val).unwrap_or_else(|val| (*val).to_owned());
+
+                Ok(Some((
+                    value,
+                    ResetToken {
+                        inner,
+                        state,
+                        published: false,
+                    },
+                )))
             }
         }
     }
 
-    pub fn reset(&mut self) -> Result<Option<T>, GuardError> {
-        let mut state = self.value.write();
+    /// Waits for the value like `wait`, resetting the guard once the returned `TakenValue` is
+    /// dropped, unless it is kept or the guard went through another transition meanwhile.
+    pub fn take_guarded(&self) -> Result<TakenValue<'_, T>, GuardError> {
+        let (value, generation) =
+            self.wait_with(0, &Cancel::SHUTDOWN, |val| (val.clone(), self.generation()))?;
 
-        match state.deref() {
-            State::UnSet | State::Killed => Ok(None),
-            State::Value(val) => {
-                let value = (**val).to_owned();
-                *state = State::UnSet;
+        Ok(TakenValue {
+            guard: self,
+            value,
+            generation,
+            keep: false,
+        })
+    }
+
+    /// Resets the guard only if the current value satisfies `pred`, returning the cleared value.
+    pub fn reset_if<F>(&self, pred: F) -> Result<Option<T>, GuardError>
+    where
+        T: Clone,
+        F: FnOnce(&T) -> bool,
+    {
+        self.inner.reset_if(pred)
+    }
+
+    /// Applies `f` to the stored value in place, clones the value first if it is shared with an
+    /// ongoing reader.
+    pub fn modify<F>(&self, f: F) -> Result<(), GuardError>
+    where
+        T: Clone,
+        F: FnOnce(&mut T),
+    {
+        self.inner.modify(f)
+    }
+
+    /// Extracts the value consuming the guard, the value is moved out when this is the last
+    /// handle and no reader holds it, otherwise it is cloned.
+    pub fn try_into_inner(self) -> Result<Option<T>, GuardError>
+    where
+        T: Clone,
+    {
+        self.inner.try_into_inner()
+    }
+
+    /// Current value without waiting, `None` when the guard is either unset or killed.
+    pub fn get(&self) -> Option<Arc<T>> {
+        self.inner.get()
+    }
+
+    /// Creates an `AsyncGuard` handle sharing the state and the config of the guard, so the value
+    /// set on either side can be awaited without blocking. The value of `from_fn` is computed by
+    /// the first wait on either side.
+    #[cfg(feature = "async")]
+    pub fn as_async(&self) -> crate::r#async::AsyncGuard<T> {
+        crate::r#async::AsyncGuard::from(self)
+    }
+
+    /// Creates a guard already holding `value`.
+    pub fn with_value(value: T, config: GuardConfig) -> Self {
+        SyncGuard {
+            inner: Inner::with_value(value, config),
+        }
+    }
+
+    /// Creates a handle sharing the state of the guard, but with its own config derived from the
+    /// current one with `f`.
+    #[must_use = "the new handle is returned, the guard itself is left unchanged"]
+    pub fn map_config<F>(&self, f: F) -> Self
+    where
+        F: FnOnce(GuardConfig) -> GuardConfig,
+    {
+        SyncGuard {
+            inner: self.inner.map_config(f),
+        }
+    }
+
+    /// Creates an independent, unset guard with a copy of the config, unlike `clone` which
+    /// shares the state and the config.
+    #[must_use = "the new handle is returned, the guard itself is left unchanged"]
+    pub fn clone_empty(&self) -> Self {
+        SyncGuard {
+            inner: self.inner.clone_empty(),
+        }
+    }
+
+    /// Changes the timeout for every clone of the guard, waits already blocking keep the timeout they started with.
+    pub fn set_timeout(&self, timeout: Timeout) {
+        self.inner.set_timeout(timeout)
+    }
+
+    /// Deadline a wait starting now would give up at, `None` unless the timeout is a `Duration`,
+    /// so an external timer can be armed along with the guard's own timeout.
+    pub fn effective_deadline(&self) -> Option<std::time::Instant> {
+        self.inner.effective_deadline()
+    }
+
+    /// Number of value transitions (set, modify, reset) the guard has gone through.
+    pub fn generation(&self) -> u64 {
+        self.inner.generation()
+    }
+
+    /// State of the guard, read without locking.
+    pub fn state(&self) -> GuardState {
+        self.inner.state()
+    }
+
+    /// Whether the guard holds a value, checked without locking.
+    pub fn is_set(&self) -> bool {
+        self.inner.state() == GuardState::Set
+    }
+
+    /// Latest state transitions of the guard with the time they happened, oldest first, up to
+    /// `history_capacity` of them.
+    #[cfg(feature = "history")]
+    pub fn recent_transitions(&self) -> Vec<(std::time::Instant, GuardState)> {
+        self.inner.recent_transitions()
+    }
+
+    /// Registers `waker` to be woken once by the next set, kill or reset, like the wakers of the
+    /// `AsyncGuard` handles of the guard.
+    #[cfg(feature = "async")]
+    pub fn register_waker(&self, waker: std::task::Waker) {
+        self.inner.register_waker(waker)
+    }
+
+    /// Number of consumers currently waiting on the guard.
+    pub fn waiter_count(&self) -> usize {
+        self.inner.waiter_count()
+    }
+
+    /// Number of handles sharing the guard, including this one.
+    pub fn handle_count(&self) -> usize {
+        self.inner.handle_count()
+    }
+
+    /// Counters of the operations the guard went through, shared by all of its handles.
+    pub fn stats(&self) -> GuardStats {
+        self.inner.stats()
+    }
+
+    /// Raises the shutdown flag of the config, waking the waits of the `AsyncGuard` handles of
+    /// the guard so they fail with `Cancelled` like the blocking ones.
+    pub fn request_shutdown(&self) {
+        self.inner.request_shutdown()
+    }
+
+    /// Current value along with its generation, without waiting.
+    pub fn snapshot(&self) -> Result<Snapshot<T>, GuardError> {
+        match self.inner.value.read().deref() {
+            State::Value(val) => Ok(Snapshot {
+                value: val.clone(),
+                generation: self.generation(),
+                guard: Arc::downgrade(&self.inner.value),
+            }),
+            State::UnSet => Err(GuardError::NotReady),
+            State::Killed => Err(GuardError::Killed),
+        }
+    }
+
+    /// Channel receiving the value once it is set, usable in `crossbeam_channel::select!`.
+    /// If the guard already has a value it is received immediately, once the guard is killed the
+    /// channel is disconnected.
+    #[cfg(feature = "crossbeam")]
+    pub fn ready_receiver(&self) -> crossbeam_channel::Receiver<Arc<T>> {
+        self.inner.ready_receiver()
+    }
+}
+
+/// Cancellation of a blocking wait besides the shutdown flag, along with how the wait pauses
+/// between state checks.
+struct Cancel<'a> {
+    should_cancel: &'a dyn Fn() -> bool,
+    pause: fn(&mut Pause, Option<std::time::Duration>),
+    /// Timeout of the wait instead of the one of the config.
+    timeout: Option<Timeout>,
+}
+
+impl Cancel<'_> {
+    /// Cancelled by the shutdown flag only, sleeping between state checks.
+    const SHUTDOWN: Cancel<'static> = Cancel {
+        should_cancel: &|| false,
+        pause: Pause::wait_within,
+        timeout: None,
+    };
+
+    /// Like `SHUTDOWN`, timing out after `max` whatever the timeout of the config.
+    fn within(max: std::time::Duration) -> Cancel<'static> {
+        Cancel {
+            timeout: Some(Timeout::Duration(max)),
+            ..Cancel::SHUTDOWN
+        }
+    }
+
+    fn is_cancelled(&self, config: &RwLock<GuardConfig>) -> bool {
+        (self.should_cancel)() || config.read().is_shut_down()
+    }
+}
+
+/// Resolved value of `SyncGuard::wait_outcome`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum WaitOutcome<T> {
+    /// The current value of the guard.
+    Value(Arc<T>),
+    /// The last value of the guard before it was reset, served after a timed out wait.
+    Stale(Arc<T>),
+    Killed,
+    TimedOut,
+}
+
+/// Interrupts `SyncGuard::wait_interruptible` on the thread which created the handle.
+#[derive(Clone, Debug)]
+pub struct InterruptHandle {
+    thread: std::thread::Thread,
+    signalled: Arc<AtomicBool>,
+}
+
+impl InterruptHandle {
+    /// Creates a handle interrupting the current thread.
+    pub fn new() -> Self {
+        InterruptHandle {
+            thread: std::thread::current(),
+            signalled: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    /// Cancels the ongoing and any later interruptible wait, unparking the thread.
+    pub fn signal(&self) {
+        self.signalled.store(true, Ordering::Release);
+        self.thread.unpark();
+    }
+
+    pub fn is_signalled(&self) -> bool {
+        self.signalled.load(Ordering::Acquire)
+    }
+}
+
+impl Default for InterruptHandle {
+    fn default() -> Self {
+        InterruptHandle::new()
+    }
+}
+
+/// Write lock of a guard emptied by `SyncGuard::take`.
+pub struct ResetToken<'a, T> {
+    inner: &'a Inner<T>,
+    state: RwLockWriteGuard<'a, State<T>>,
+    /// Whether a replacement was published, which already went through the transition.
+    published: bool,
+}
+
+impl<T> ResetToken<'_, T> {
+    /// Publishes `value` as the replacement of the taken value, under the same lock.
+    pub fn set(mut self, value: T) {
+        self.inner.publish(&mut self.state, Arc::new(value));
+        self.published = true;
+    }
+}
+
+/// The tag is only updated here, so the taken value stays visible until the lock is released.
+impl<T> Drop for ResetToken<'_, T> {
+    fn drop(&mut self) {
+        if self.published {
+            return;
+        }
+        self.inner.transition(self.state.deref());
+        #[cfg(feature = "async")]
+        self.inner.wakers.lock().wake_all();
+    }
+}
+
+/// Value waited for by `SyncGuard::take_guarded`, resetting the guard on drop.
+pub struct TakenValue<'a, T> {
+    guard: &'a SyncGuard<T>,
+    value: Arc<T>,
+    /// Generation of the guard when the value was taken.
+    generation: u64,
+    keep: bool,
+}
+
+impl<T> TakenValue<'_, T> {
+    /// Leaves the value in the guard when dropped.
+    pub fn keep(mut self) -> Arc<T> {
+        self.keep = true;
+        self.value.clone()
+    }
+}
+
+impl<T> Deref for TakenValue<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        &self.value
+    }
+}
+
+impl<T> Drop for TakenValue<'_, T> {
+    fn drop(&mut self) {
+        if !self.keep {
+            // a value set after the take is left for the next consumer
+            self.guard
+                .inner
+                .clear_if_current(&self.value, self.generation);
+        }
+    }
+}
+
+/// Value of a guard captured at a given generation.
+#[derive(Clone, Debug)]
+pub struct Snapshot<T> {
+    value: Arc<T>,
+    generation: u64,
+    /// State of the guard the value was captured from, only compared by address.
+    guard: Weak<RwLock<State<T>>>,
+}
+
+impl<T> Snapshot<T> {
+    pub fn value(&self) -> &Arc<T> {
+        &self.value
+    }
+
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    /// Whether `guard` still holds the captured value, checked without locking. A snapshot of
+    /// another guard is never current, whatever its generation.
+    pub fn is_current(&self, guard: &SyncGuard<T>) -> bool {
+        std::ptr::eq(self.guard.as_ptr(), guard.inner.as_ptr())
+            && guard.generation() == self.generation
+    }
+}
+
+/// Consumer of a guard caching the last value it read, served without locking the guard as
+/// long as the generation of the guard is unchanged.
+pub struct CachedConsumer<T> {
+    guard: SyncGuard<T>,
+    cached: Option<Snapshot<T>>,
+}
+
+impl<T> CachedConsumer<T> {
+    pub fn new(guard: SyncGuard<T>) -> Self {
+        CachedConsumer {
+            guard,
+            cached: None,
+        }
+    }
+
+    /// Current value without waiting, from the cache when it is still current.
+    pub fn try_wait(&mut self) -> Result<Arc<T>, GuardError> {
+        match &self.cached {
+            Some(cached) if cached.is_current(&self.guard) => Ok(cached.value().clone()),
+            _ => {
+                let snapshot = self.guard.snapshot()?;
+                let value = snapshot.value().clone();
+                self.cached = Some(snapshot);
+
+                Ok(value)
+            }
+        }
+    }
+}
+
+/// Blocks until any of the `guards` has a value, returning its index and value. Fails like
+/// `wait_all` once every guard is killed (with `Killed` for an empty `guards`), or `Timeout` when
+/// the deadline passes. The guards are checked in a single loop, see `poll_guards`.
+pub fn wait_any<T>(
+    guards: &[SyncGuard<T>],
+    timeout: Timeout,
+) -> Result<(usize, Arc<T>), GuardError> {
+    poll_guards(guards, timeout, || {
+        let mut killed = 0;
+        for (idx, guard) in guards.iter().enumerate() {
+            match guard.inner.inspect(guard.inner.value.read().deref()) {
+                State::Value(val) => return Some(Ok((idx, val.clone()))),
+                State::Killed => killed += 1,
+                State::UnSet => {}
+            }
+        }
 
-                Ok(Some(value))
+        // failing with the error of the first guard, like `wait_all`
+        let err = guards
+            .first()
+            .map_or(GuardError::Killed, |guard| guard.inner.killed());
+        (killed == guards.len()).then_some(Err(err))
+    })
+}
+
+/// Blocks until every one of the `guards` has a value, returning the values in the order of
+/// `guards`. Fails with the error of a killed guard as soon as any guard is killed, or `Timeout`
+/// when the deadline passes. Only the unresolved guards are checked in each round of a single
+/// loop, see `poll_guards`.
+pub fn wait_all<T>(guards: &[SyncGuard<T>], timeout: Timeout) -> Result<Vec<Arc<T>>, GuardError> {
+    let mut values: Vec<Option<Arc<T>>> = vec![None; guards.len()];
+    poll_guards(guards, timeout, || {
+        for (guard, value) in guards.iter().zip(values.iter_mut()) {
+            if value.is_some() {
+                continue;
             }
+            match guard.inner.inspect(guard.inner.value.read().deref()) {
+                State::Value(val) => *value = Some(val.clone()),
+                State::Killed => return Some(Err(guard.inner.killed())),
+                State::UnSet => {}
+            }
+        }
+
+        let resolved = values.iter().all(Option::is_some);
+        resolved.then(|| Ok(values.iter().flatten().cloned().collect()))
+    })
+}
+
+/// Checks the `guards` in rounds until `check` settles the wait, as a single loop instead of one
+/// wait per guard. Each guard is initialized first, and the shutdown flag of any of them cancels
+/// the wait. Between rounds it pauses by the wait strategy of the guard due the soonest, for no
+/// longer than the time left, and `Timeout::AtLeast` holds a result back until its minimum passed.
+fn poll_guards<T, R, F>(
+    guards: &[SyncGuard<T>],
+    timeout: Timeout,
+    mut check: F,
+) -> Result<R, GuardError>
+where
+    F: FnMut() -> Option<Result<R, GuardError>>,
+{
+    let mut pauses: Vec<Pause> = guards
+        .iter()
+        .map(|guard| {
+            guard.inner.initialize();
+            guard.inner.config.read().pause()
+        })
+        .collect();
+    let t0 = std::time::Instant::now();
+    let mut polls = 0;
+    loop {
+        if guards
+            .iter()
+            .any(|guard| guard.inner.config.read().is_shut_down())
+        {
+            return Err(GuardError::Cancelled);
+        }
+        let held = match timeout {
+            Timeout::AtLeast(min) => min.checked_sub(t0.elapsed()),
+            _ => None,
+        };
+        match check() {
+            Some(Err(err)) => return Err(err),
+            Some(Ok(result)) if held.is_none() => return Ok(result),
+            _ => {}
+        }
+
+        polls += 1;
+        match timeout {
+            Timeout::Instant => return Err(GuardError::NotReady),
+            timeout if timeout.is_exceeded(t0, polls) => return Err(GuardError::Timeout),
+            _ => {}
+        }
+        if let Some(pause) = Pause::soonest(&mut pauses) {
+            pause.wait_within(held.or(timeout.remaining(t0)));
+        }
+    }
+}
+
+pub trait WaitResult<V, E> {
+    fn wait_result(&self) -> Result<Arc<V>, ResultGuardError<E>>;
+}
+
+impl<V: Clone, E: Clone> WaitResult<V, E> for SyncGuard<Result<V, E>> {
+    fn wait_result(&self) -> Result<Arc<V>, ResultGuardError<E>> {
+        match self.wait()?.as_ref() {
+            Ok(value) => Ok(Arc::new(value.to_owned())),
+            Err(err) => Err(ResultGuardError::Failed(err.to_owned())),
         }
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::error::GuardError;
-    use crate::sync::SyncGuard;
-    use crate::{GuardConfig, Timeout};
+    use crate::error::{GuardError, ResultGuardError};
+    use crate::sync::{
+        wait_all, wait_any, CachedConsumer, InterruptHandle, SyncGuard, WaitOutcome, WaitResult,
+    };
+    use crate::{
+        DefaultGuardConfig, GuardConfig, GuardState, GuardStats, NotifyPolicy, SetPolicy, Timeout,
+        WaitStrategy,
+    };
+    use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+    use std::sync::Arc;
     use std::time::Duration;
 
     const EPSILON_MILLIS: u128 = 10;
@@ -113,7 +932,7 @@ mod tests {
     #[test]
     fn test_wait_for_value() {
         let guard = SyncGuard::default();
-        let mut t_guard = guard.clone();
+        let t_guard = guard.clone();
         let _t = std::thread::spawn(move || {
             let t0 = std::time::Instant::now();
             std::thread::sleep(Duration::from_millis(100));
@@ -129,9 +948,10 @@ mod tests {
     fn test_wait_for_value_with_timeout() {
         let config = GuardConfig {
             timeout: Timeout::Duration(Duration::from_millis(120)),
+            ..Default::default()
         };
         let guard = SyncGuard::new(config);
-        let mut t_guard = guard.clone();
+        let t_guard = guard.clone();
         let _t = std::thread::spawn(move || {
             let t0 = std::time::Instant::now();
             std::thread::sleep(std::time::Duration::from_millis(100));
@@ -148,9 +968,10 @@ mod tests {
         let timeout = Duration::from_millis(50);
         let config = GuardConfig {
             timeout: Timeout::Duration(timeout),
+            ..Default::default()
         };
         let guard = SyncGuard::new(config);
-        let mut t_guard = guard.clone();
+        let t_guard = guard.clone();
         let _t = std::thread::spawn(move || {
             std::thread::sleep(Duration::from_millis(100));
             assert!(t_guard.set(42u8).is_ok());
@@ -166,9 +987,10 @@ mod tests {
     fn test_wait_for_value_with_multiple_timout() {
         let config = GuardConfig {
             timeout: Timeout::Duration(Duration::from_millis(60)),
+            ..Default::default()
         };
         let guard = SyncGuard::new(config);
-        let mut t_guard = guard.clone();
+        let t_guard = guard.clone();
         let _t = std::thread::spawn(move || {
             std::thread::sleep(Duration::from_millis(100));
             assert!(t_guard.set(42u8).is_ok());
@@ -180,7 +1002,7 @@ mod tests {
 
     #[test]
     fn test_wait_killed() {
-        let mut guard = SyncGuard::<u8>::default();
+        let guard = SyncGuard::<u8>::default();
         let t_guard = guard.clone();
         let t = std::thread::spawn(move || {
             let result = t_guard.wait();
@@ -193,27 +1015,468 @@ mod tests {
     }
 
     #[test]
-    fn test_value_set_after_kill() {
-        let mut guard = SyncGuard::default();
-        let mut t_guard = guard.clone();
-        let t = std::thread::spawn(move || {
-            std::thread::sleep(Duration::from_millis(100));
-            let result = t_guard.set(42u8);
-            assert!(result.is_err());
-            assert_eq!(result.unwrap_err(), GuardError::Killed);
-        });
+    fn test_shared_guard() {
+        let config = GuardConfig {
+            poll_interval: Some(Duration::from_millis(1)),
+            ..Default::default()
+        };
+        let guard = SyncGuard::<usize>::new(config).shared();
+        let threads: Vec<_> = (0..16)
+            .map(|_| {
+                let guard = guard.clone();
+                std::thread::spawn(move || *guard.wait().unwrap())
+            })
+            .collect();
+
+        assert!(guard.set(42).is_ok());
+        for t in threads {
+            assert_eq!(t.join().expect("failed to wait guard thread"), 42);
+        }
+        assert_eq!(guard.reset(), Ok(Some(42)));
+        assert!(guard.kill().is_ok());
+    }
+
+    #[test]
+    fn test_wait_interruptible() {
+        let config = GuardConfig {
+            poll_interval: Some(Duration::from_secs(1)),
+            ..Default::default()
+        };
+        let guard = SyncGuard::<u8>::new(config);
+        let interrupt = InterruptHandle::new();
+        let t_interrupt = interrupt.clone();
+        let _t = std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(50));
+            t_interrupt.signal();
+        });
+
+        let t0 = std::time::Instant::now();
+        assert_eq!(
+            guard.wait_interruptible(&interrupt).unwrap_err(),
+            GuardError::Cancelled
+        );
+        assert!(t0.elapsed().as_millis() < 50 + EPSILON_MILLIS);
+        assert_eq!(guard.waiter_count(), 0);
+    }
+
+    #[test]
+    fn test_wait_until_cancel() {
+        let config = GuardConfig {
+            poll_interval: Some(Duration::from_millis(1)),
+            ..Default::default()
+        };
+        let guard = SyncGuard::<u8>::new(config);
+
+        let t0 = std::time::Instant::now();
+        let should_cancel = || t0.elapsed() > Duration::from_millis(50);
+        assert_eq!(
+            guard.wait_until_cancel(should_cancel).unwrap_err(),
+            GuardError::Cancelled
+        );
+        assert!(t0.elapsed().as_millis() < 50 + EPSILON_MILLIS);
+        assert_eq!(guard.waiter_count(), 0);
+
+        // the cancellable wait honors the policies of a plain wait
+        let config = GuardConfig {
+            timeout: Timeout::AtLeast(Duration::from_millis(30)),
+            notify_policy: NotifyPolicy::Once,
+            poll_interval: Some(Duration::from_millis(1)),
+            ..Default::default()
+        };
+        let guard = SyncGuard::<u8>::new(config);
+        assert!(guard.set(42).is_ok());
+        let t0 = std::time::Instant::now();
+        assert_eq!(guard.wait_until_cancel(|| false), Ok(Arc::new(42)));
+        assert!(t0.elapsed() >= Duration::from_millis(30));
+        assert_eq!(
+            guard.wait_until_cancel(|| false).unwrap_err(),
+            GuardError::Consumed
+        );
+        assert_eq!(guard.stats().resolved, 1);
+    }
+
+    #[test]
+    fn test_configured_from_value_type() {
+        struct Response;
+
+        impl DefaultGuardConfig for Response {
+            fn default_guard_config() -> GuardConfig {
+                GuardConfig::timeout_secs(30)
+            }
+        }
+
+        struct Plain;
+
+        impl DefaultGuardConfig for Plain {}
+
+        let guard = SyncGuard::<Response>::configured();
+        assert_eq!(
+            guard.inner.config.read().timeout,
+            Timeout::Duration(Duration::from_secs(30))
+        );
+        let guard = SyncGuard::<Plain>::configured();
+        assert_eq!(guard.inner.config.read().timeout, Timeout::Infinite);
+    }
+
+    #[test]
+    fn test_map_config_shares_value() {
+        let config = GuardConfig {
+            timeout: Timeout::Duration(Duration::from_millis(20)),
+            ..Default::default()
+        };
+        let guard = SyncGuard::<u8>::new(config);
+        let derived = guard.map_config(|config| match config.timeout {
+            Timeout::Duration(timeout) => GuardConfig {
+                timeout: Timeout::Duration(timeout * 2),
+                ..config
+            },
+            _ => config,
+        });
+        assert_eq!(
+            derived.inner.config.read().timeout,
+            Timeout::Duration(Duration::from_millis(40))
+        );
+        assert_eq!(
+            guard.inner.config.read().timeout,
+            Timeout::Duration(Duration::from_millis(20))
+        );
+
+        assert!(guard.set(42).is_ok());
+        assert_eq!(*derived.wait().unwrap(), 42);
+        assert_eq!(derived, guard);
+
+        let guard = SyncGuard::with_value(7u8, GuardConfig::INSTANT);
+        assert_eq!(*guard.wait().unwrap(), 7);
+        assert!(guard.is_set());
+    }
+
+    #[test]
+    fn test_guard_non_clone_value() {
+        struct Connection(AtomicUsize);
+
+        let guard = SyncGuard::<Connection>::new(GuardConfig::timeout_secs(1));
+        let t_guard = guard.clone();
+        let consumer = std::thread::spawn(move || {
+            let conn = t_guard.wait().unwrap();
+            conn.0.fetch_add(1, Ordering::AcqRel)
+        });
+
+        let conn = Arc::new(Connection(AtomicUsize::new(41)));
+        assert!(guard.set_arc(conn.clone()).is_ok());
+        assert_eq!(consumer.join().expect("failed to join consumer thread"), 41);
+        assert_eq!(guard.get().unwrap().0.load(Ordering::Acquire), 42);
+        assert!(Arc::ptr_eq(&guard.get().unwrap(), &conn));
+        assert_eq!(guard.kill().unwrap_err(), GuardError::UnableToKilled);
+        assert!(guard.force_kill().is_ok());
+    }
+
+    #[test]
+    fn test_handle_count() {
+        let guard = SyncGuard::<u8>::default();
+        assert_eq!(guard.handle_count(), 1);
+
+        let clones = vec![guard.clone(), guard.clone(), guard.clone()];
+        assert_eq!(guard.handle_count(), 4);
+        assert_eq!(guard.clone_empty().handle_count(), 1);
+
+        drop(clones);
+        assert_eq!(guard.handle_count(), 1);
+    }
+
+    #[test]
+    fn test_wait_outcome() {
+        let config = GuardConfig {
+            timeout: Timeout::Duration(Duration::from_millis(20)),
+            ..Default::default()
+        };
+        let guard = SyncGuard::<u8>::new(config);
+        assert_eq!(guard.wait_outcome(), Ok(WaitOutcome::TimedOut));
+        assert!(guard.set(42).is_ok());
+        assert_eq!(guard.wait_outcome(), Ok(WaitOutcome::Value(Arc::new(42))));
+        assert!(guard.reset().is_ok());
+        assert_eq!(guard.wait_outcome(), Ok(WaitOutcome::TimedOut));
+        assert!(guard.kill().is_ok());
+        assert_eq!(guard.wait_outcome(), Ok(WaitOutcome::Killed));
+    }
+
+    #[test]
+    fn test_wait_outcome_serves_stale_value() {
+        let config = GuardConfig {
+            timeout: Timeout::Duration(Duration::from_millis(20)),
+            serve_stale_on_timeout: true,
+            ..Default::default()
+        };
+        let guard = SyncGuard::<u8>::new(config);
+        assert_eq!(guard.wait_outcome(), Ok(WaitOutcome::TimedOut));
+
+        assert!(guard.set(42).is_ok());
+        assert_eq!(guard.wait_outcome(), Ok(WaitOutcome::Value(Arc::new(42))));
+        assert!(guard.reset().is_ok());
+        assert_eq!(guard.wait_outcome(), Ok(WaitOutcome::Stale(Arc::new(42))));
+        assert_eq!(guard.wait().unwrap_err(), GuardError::Timeout);
+    }
+
+    #[test]
+    fn test_notify_one_consumes_value() {
+        let config = GuardConfig {
+            timeout: Timeout::Duration(Duration::from_millis(300)),
+            poll_interval: Some(Duration::from_millis(1)),
+            notify_policy: NotifyPolicy::One,
+            ..Default::default()
+        };
+        let guard = SyncGuard::<u8>::new(config);
+        let waiters: Vec<_> = (0..3)
+            .map(|_| {
+                let guard = guard.clone();
+                std::thread::spawn(move || guard.wait())
+            })
+            .collect();
+
+        std::thread::sleep(Duration::from_millis(50));
+        assert!(guard.set(1).is_ok());
+        std::thread::sleep(Duration::from_millis(50));
+        assert!(guard.set(2).is_ok());
+
+        let mut values: Vec<_> = waiters
+            .into_iter()
+            .filter_map(|t| t.join().expect("failed to wait guard thread").ok())
+            .map(|value| *value)
+            .collect();
+        values.sort();
+        assert_eq!(values, vec![1, 2]);
+    }
+
+    #[test]
+    fn test_notify_once_late_waiter_consumed() {
+        let config = GuardConfig {
+            timeout: Timeout::Duration(Duration::from_millis(300)),
+            poll_interval: Some(Duration::from_millis(1)),
+            notify_policy: NotifyPolicy::Once,
+            ..Default::default()
+        };
+        let guard = SyncGuard::<u8>::new(config);
+        let t_guard = guard.clone();
+        let t = std::thread::spawn(move || t_guard.wait());
+        std::thread::sleep(Duration::from_millis(20));
+        assert!(guard.set(42).is_ok());
+        assert_eq!(
+            t.join().expect("failed to wait guard thread").as_deref(),
+            Ok(&42)
+        );
+
+        let t0 = std::time::Instant::now();
+        assert_eq!(guard.wait(), Err(GuardError::Consumed));
+        assert!(t0.elapsed().as_millis() < EPSILON_MILLIS);
+
+        assert!(guard.set(43).is_ok());
+        assert_eq!(guard.wait().as_deref(), Ok(&43));
+    }
+
+    #[test]
+    fn test_notify_one_serves_higher_priority_first() {
+        let config = GuardConfig {
+            timeout: Timeout::Duration(Duration::from_millis(200)),
+            poll_interval: Some(Duration::from_millis(1)),
+            notify_policy: NotifyPolicy::One,
+            ..Default::default()
+        };
+        let guard = SyncGuard::<u8>::new(config);
+        let t_guard = guard.clone();
+        let low = std::thread::spawn(move || t_guard.wait());
+        let t_guard = guard.clone();
+        let high = std::thread::spawn(move || t_guard.wait_with_priority(10));
+        while guard.waiter_count() < 2 {
+            std::thread::yield_now();
+        }
+
+        assert!(guard.set(42).is_ok());
+        assert_eq!(*high.join().expect("failed to join waiter").unwrap(), 42);
+        assert_eq!(
+            low.join().expect("failed to join waiter").unwrap_err(),
+            GuardError::Timeout
+        );
+    }
+
+    #[test]
+    fn test_slow_wait_reported() {
+        let slow_waits = Arc::new(AtomicUsize::new(0));
+        let t_slow_waits = slow_waits.clone();
+        let config = GuardConfig {
+            slow_wait_threshold: Some(Duration::from_millis(50)),
+            on_slow_wait: Some(Arc::new(move |elapsed| {
+                assert!(elapsed >= Duration::from_millis(50));
+                t_slow_waits.fetch_add(1, Ordering::SeqCst);
+            })),
+            ..Default::default()
+        };
+        let guard = SyncGuard::<u8>::new(config);
+        let t_guard = guard.clone();
+        let _t = std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(100));
+            assert!(t_guard.set(42).is_ok());
+        });
+
+        assert_eq!(*guard.wait().unwrap(), 42);
+        assert_eq!(*guard.wait().unwrap(), 42);
+        assert_eq!(slow_waits.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn test_wait_at_least() {
+        let config = GuardConfig {
+            timeout: Timeout::AtLeast(Duration::from_millis(100)),
+            poll_interval: Some(Duration::from_millis(1)),
+            ..Default::default()
+        };
+        let guard = SyncGuard::<u8>::new(config);
+        let t_guard = guard.clone();
+        let _t = std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(20));
+            assert!(t_guard.set(42).is_ok());
+        });
+
+        let (value, elapsed) = guard.wait_timed().unwrap();
+        assert_eq!(*value, 42);
+        assert!(elapsed.as_millis() >= 100);
+        assert!(elapsed.as_millis() < 100 + EPSILON_MILLIS);
+
+        let guard = guard.clone_empty();
+        assert!(guard.kill().is_ok());
+        let t0 = std::time::Instant::now();
+        assert_eq!(guard.wait().unwrap_err(), GuardError::Killed);
+        assert!(t0.elapsed().as_millis() < EPSILON_MILLIS);
+    }
+
+    #[test]
+    fn test_try_wait_for() {
+        let guard = SyncGuard::<u8>::default();
+        let max = Duration::from_millis(50);
+        assert_eq!(guard.try_wait_for(max), Ok(None));
+
+        let t_guard = guard.clone();
+        let t = std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(20));
+            assert!(t_guard.set(42).is_ok());
+        });
+        assert_eq!(guard.try_wait_for(max), Ok(Some(Arc::new(42))));
+        t.join().expect("failed to wait guard thread");
+
+        let guard = SyncGuard::<u8>::default();
+        assert!(guard.kill().is_ok());
+        assert_eq!(guard.try_wait_for(max), Err(GuardError::Killed));
+    }
+
+    #[test]
+    fn test_try_wait_for_goes_through_wait() {
+        let guard = SyncGuard::from_fn(|| 42u8, GuardConfig::default());
+        let max = Duration::from_millis(50);
+        assert_eq!(guard.try_wait_for(max), Ok(Some(Arc::new(42))));
+        assert_eq!(guard.stats().resolved, 1);
+
+        let guard = SyncGuard::<u8>::new(GuardConfig {
+            poll_interval: Some(Duration::from_secs(1)),
+            ..Default::default()
+        });
+        let t0 = std::time::Instant::now();
+        assert_eq!(guard.try_wait_for(max), Ok(None));
+        // the pause between state checks is capped by the time left
+        assert!(t0.elapsed() < max + Duration::from_millis(EPSILON_MILLIS as u64 * 5));
+        assert_eq!(guard.stats().timeouts, 1);
+    }
+
+    #[test]
+    fn test_wait_for_kill() {
+        let guard = SyncGuard::<u8>::default();
+        let t_guard = guard.clone();
+        let t = std::thread::spawn(move || {
+            let t0 = std::time::Instant::now();
+            assert_eq!(t_guard.wait_for_kill(), Ok(None));
+            t0.elapsed()
+        });
+        std::thread::sleep(Duration::from_millis(50));
+        assert!(guard.kill().is_ok());
+        let elapsed = t.join().expect("failed to wait guard thread");
+        assert!(elapsed.as_millis() < 50 + EPSILON_MILLIS * 5);
+
+        let guard = SyncGuard::<u8>::default();
+        assert!(guard.set(42).is_ok());
+        assert_eq!(guard.wait_for_kill(), Err(GuardError::AlreadySet));
+
+        let guard = SyncGuard::<u8>::new(GuardConfig::INSTANT);
+        assert_eq!(guard.wait_for_kill(), Err(GuardError::NotReady));
+    }
+
+    #[test]
+    fn test_set_result_err_fails_waiters() {
+        let guard = SyncGuard::<u8>::default();
+        let t_guard = guard.clone();
+        let t = std::thread::spawn(move || t_guard.wait());
+        std::thread::sleep(Duration::from_millis(20));
+        let result = Err(std::io::Error::other("upstream down"));
+        assert!(guard.set_result(result).is_ok());
+
+        let failed = GuardError::Failed(String::from("upstream down"));
+        assert_eq!(
+            t.join().expect("failed to wait guard thread"),
+            Err(failed.clone())
+        );
+        assert_eq!(guard.wait(), Err(failed));
+        assert_eq!(
+            guard.wait_for_kill(),
+            Ok(Some(String::from("upstream down")))
+        );
+
+        let guard = SyncGuard::<u8>::default();
+        assert!(guard.set_result(Ok::<_, std::io::Error>(42)).is_ok());
+        assert_eq!(guard.wait().as_deref(), Ok(&42));
+    }
+
+    #[test]
+    fn test_value_set_after_kill() {
+        let guard = SyncGuard::default();
+        let t_guard = guard.clone();
+        let t = std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(100));
+            let result = t_guard.set(42u8);
+            assert!(result.is_err());
+            assert_eq!(result.unwrap_err(), GuardError::Killed);
+        });
 
         std::thread::sleep(Duration::from_millis(50));
         assert!(guard.kill().is_ok());
         t.join().expect("failed to wait guard thread")
     }
 
+    #[test]
+    fn test_clear_skips_clone() {
+        static CLONES: AtomicUsize = AtomicUsize::new(0);
+
+        struct Counted;
+        impl Clone for Counted {
+            fn clone(&self) -> Self {
+                CLONES.fetch_add(1, Ordering::SeqCst);
+                Counted
+            }
+        }
+
+        let guard = SyncGuard::<Counted>::default();
+        assert_eq!(guard.clear(), Ok(false));
+        assert!(guard.set(Counted).is_ok());
+        assert_eq!(guard.clear(), Ok(true));
+        assert_eq!(guard.clear(), Ok(false));
+        assert_eq!(CLONES.load(Ordering::SeqCst), 0);
+
+        assert!(guard.kill().is_ok());
+        assert_eq!(guard.clear(), Ok(false));
+    }
+
     #[test]
     fn test_resetting_a_value() {
         let config = GuardConfig {
             timeout: Timeout::Duration(Duration::from_millis(100)),
+            ..Default::default()
         };
-        let mut guard = SyncGuard::<u8>::new(config);
+        let guard = SyncGuard::<u8>::new(config);
         assert!(guard.set(42).is_ok());
 
         assert!(guard.wait().is_ok());
@@ -228,21 +1491,954 @@ mod tests {
 
     #[test]
     fn test_killed_an_elapsed_guard() {
-        let mut guard = SyncGuard::<u8>::default();
+        let guard = SyncGuard::<u8>::default();
         assert!(guard.set(42).is_ok());
         assert!(guard.kill().is_err());
         assert_eq!(guard.kill().unwrap_err(), GuardError::UnableToKilled);
     }
 
+    #[test]
+    fn test_force_kill_an_elapsed_guard() {
+        let guard = SyncGuard::<u8>::default();
+        assert!(guard.set(42).is_ok());
+        assert!(guard.force_kill().is_ok());
+        assert_eq!(guard.wait().unwrap_err(), GuardError::Killed);
+        assert_eq!(guard.clone().wait().unwrap_err(), GuardError::Killed);
+        assert_eq!(guard.set(42).unwrap_err(), GuardError::Killed);
+    }
+
     #[test]
     fn test_instant_retrieving_value() {
         let config = GuardConfig {
             timeout: Timeout::Instant,
+            ..Default::default()
         };
-        let mut guard = SyncGuard::<u8>::new(config);
-        assert!(guard.wait().is_err());
+        let guard = SyncGuard::<u8>::new(config);
+        assert_eq!(guard.wait().unwrap_err(), GuardError::NotReady);
         assert!(guard.set(42).is_ok());
         assert!(guard.wait().is_ok());
         assert_eq!(*guard.wait().unwrap(), 42);
     }
+
+    #[test]
+    fn test_lazy_value_computed_once() {
+        let counter = Arc::new(AtomicUsize::new(0));
+        let t_counter = counter.clone();
+        let guard = SyncGuard::from_fn(
+            move || {
+                std::thread::sleep(Duration::from_millis(50));
+                t_counter.fetch_add(1, Ordering::SeqCst) + 42
+            },
+            GuardConfig::default(),
+        );
+
+        let waiters: Vec<_> = (0..4)
+            .map(|_| {
+                let t_guard = guard.clone();
+                std::thread::spawn(move || *t_guard.wait().unwrap())
+            })
+            .collect();
+
+        for waiter in waiters {
+            assert_eq!(waiter.join().expect("failed to wait guard thread"), 42);
+        }
+        assert_eq!(*guard.wait().unwrap(), 42);
+        assert_eq!(counter.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn test_from_fn_panic_fails_waiters() {
+        let guard = SyncGuard::<u8>::from_fn(
+            || {
+                std::thread::sleep(Duration::from_millis(50));
+                panic!("initializer failed")
+            },
+            GuardConfig {
+                timeout: Timeout::Infinite,
+                ..Default::default()
+            },
+        );
+
+        let t_guard = guard.clone();
+        let initializer = std::thread::spawn(move || t_guard.wait());
+        std::thread::sleep(Duration::from_millis(10));
+        assert!(matches!(guard.wait(), Err(GuardError::Failed(_))));
+        assert!(initializer.join().is_err());
+        assert_eq!(guard.state(), GuardState::Killed);
+    }
+
+    #[test]
+    fn test_wait_result() {
+        let guard = SyncGuard::<Result<u8, String>>::default();
+        assert!(guard.set(Ok(42)).is_ok());
+        assert_eq!(*guard.wait_result().unwrap(), 42);
+
+        assert!(guard.reset().is_ok());
+        assert!(guard.set(Err(String::from("failed"))).is_ok());
+        assert_eq!(
+            guard.wait_result().unwrap_err(),
+            ResultGuardError::Failed(String::from("failed"))
+        );
+
+        assert!(guard.reset().is_ok());
+        assert!(guard.kill().is_ok());
+        assert_eq!(
+            guard.wait_result().unwrap_err(),
+            ResultGuardError::Guard(GuardError::Killed)
+        );
+    }
+
+    #[test]
+    fn test_wait_map_err() {
+        #[derive(Debug, PartialEq)]
+        enum AppError {
+            Unavailable,
+            Other,
+        }
+
+        let config = GuardConfig {
+            timeout: Timeout::Duration(Duration::from_millis(20)),
+            ..Default::default()
+        };
+        let guard = SyncGuard::<u8>::new(config);
+        let to_app_error = |err| match err {
+            GuardError::Timeout => AppError::Unavailable,
+            _ => AppError::Other,
+        };
+        assert_eq!(guard.wait_map_err(to_app_error), Err(AppError::Unavailable));
+
+        assert!(guard.set(42).is_ok());
+        assert_eq!(guard.wait_map_err(to_app_error), Ok(Arc::new(42)));
+    }
+
+    #[test]
+    fn test_take_and_replace() {
+        let guard = SyncGuard::<u32>::default();
+        assert!(guard.take().unwrap().is_none());
+        assert!(guard.set(0).is_ok());
+
+        let done = Arc::new(AtomicBool::new(false));
+        let (t_guard, t_done) = (guard.clone(), done.clone());
+        let reader = std::thread::spawn(move || {
+            while !t_done.load(Ordering::Acquire) {
+                assert!(t_guard.snapshot().is_ok());
+            }
+        });
+        for _ in 0..1000 {
+            let (value, token) = guard.take().unwrap().unwrap();
+            token.set(value + 1);
+        }
+        done.store(true, Ordering::Release);
+        reader.join().expect("failed to join reader thread");
+        assert_eq!(*guard.wait().unwrap(), 1000);
+        assert_eq!(guard.stats().sets, 1001);
+        assert_eq!(guard.stats().resets, 1000);
+
+        let (_, token) = guard.take().unwrap().unwrap();
+        drop(token);
+        assert_eq!(guard.snapshot().unwrap_err(), GuardError::NotReady);
+        assert!(guard.kill().is_ok());
+        assert_eq!(guard.take().err(), Some(GuardError::Killed));
+    }
+
+    #[test]
+    fn test_state_tag_follows_transitions() {
+        let guard = SyncGuard::<u8>::default();
+        let check = |state: GuardState| {
+            assert_eq!(guard.state(), state);
+            assert_eq!(guard.is_set(), state == GuardState::Set);
+            assert_eq!(guard.get().is_some(), state == GuardState::Set);
+        };
+        check(GuardState::UnSet);
+        assert!(guard.set(1).is_ok());
+        check(GuardState::Set);
+        assert_eq!(guard.reset(), Ok(Some(1)));
+        check(GuardState::UnSet);
+
+        assert!(guard.set(2).is_ok());
+        let (value, token) = guard.take().unwrap().unwrap();
+        assert!(guard.is_set());
+        drop(token);
+        check(GuardState::UnSet);
+        assert!(guard.set(value).is_ok());
+        let (value, token) = guard.take().unwrap().unwrap();
+        token.set(value + 1);
+        check(GuardState::Set);
+        assert_eq!(guard.clear(), Ok(true));
+        check(GuardState::UnSet);
+
+        assert!(guard.kill().is_ok());
+        check(GuardState::Killed);
+        assert!(guard.force_kill().is_ok());
+        check(GuardState::Killed);
+
+        let config = GuardConfig {
+            notify_policy: NotifyPolicy::One,
+            ..Default::default()
+        };
+        let guard = SyncGuard::<u8>::from_fn(|| 3, config);
+        assert_eq!(*guard.wait().unwrap(), 3);
+        assert_eq!(guard.state(), GuardState::UnSet);
+    }
+
+    #[test]
+    fn test_set_if_changed() {
+        let guard = SyncGuard::<u8>::default();
+        assert_eq!(guard.set_if_changed(1), Ok(true));
+        let generation = guard.generation();
+        assert_eq!(guard.set_if_changed(1), Ok(false));
+        assert_eq!(guard.generation(), generation);
+        assert_eq!(guard.set_if_changed(2), Ok(true));
+        assert_eq!(guard.generation(), generation + 1);
+
+        assert!(guard.force_kill().is_ok());
+        assert_eq!(guard.set_if_changed(2), Err(GuardError::Killed));
+    }
+
+    #[cfg(feature = "history")]
+    #[test]
+    fn test_recent_transitions() {
+        let config = GuardConfig {
+            history_capacity: 4,
+            ..Default::default()
+        };
+        let guard = SyncGuard::<u8>::new(config);
+        for value in 0..3 {
+            assert!(guard.set(value).is_ok());
+            assert!(guard.clear().is_ok());
+        }
+        assert!(guard.kill().is_ok());
+
+        let history = guard.recent_transitions();
+        let states: Vec<_> = history.iter().map(|(_, state)| *state).collect();
+        assert_eq!(
+            states,
+            vec![
+                GuardState::UnSet,
+                GuardState::Set,
+                GuardState::UnSet,
+                GuardState::Killed
+            ]
+        );
+        assert!(history.windows(2).all(|pair| pair[0].0 <= pair[1].0));
+    }
+
+    #[test]
+    fn test_take_guarded() {
+        let guard = SyncGuard::<u8>::default();
+        assert!(guard.set(42).is_ok());
+
+        let taken = guard.take_guarded().unwrap();
+        assert_eq!(*taken, 42);
+        assert_eq!(*guard.get().unwrap(), 42);
+        drop(taken);
+        assert_eq!(guard.snapshot().unwrap_err(), GuardError::NotReady);
+
+        assert!(guard.set(7).is_ok());
+        assert_eq!(*guard.take_guarded().unwrap().keep(), 7);
+        assert_eq!(*guard.get().unwrap(), 7);
+
+        let taken = guard.take_guarded().unwrap();
+        assert!(guard.set(8).is_ok());
+        drop(taken);
+        assert_eq!(*guard.get().unwrap(), 8);
+    }
+
+    #[test]
+    fn test_modify_value_in_place() {
+        let guard = SyncGuard::<Vec<u8>>::default();
+        assert_eq!(
+            guard.modify(|v| v.push(1)).unwrap_err(),
+            GuardError::NotReady
+        );
+
+        assert!(guard.set(vec![42]).is_ok());
+        let generation = guard.generation();
+        let before = guard.wait().unwrap();
+
+        assert!(guard.modify(|v| v.push(43)).is_ok());
+        assert_eq!(guard.generation(), generation + 1);
+        assert_eq!(*guard.wait().unwrap(), vec![42, 43]);
+        assert_eq!(*before, vec![42]);
+    }
+
+    #[test]
+    fn test_wait_with_poll_interval() {
+        let interval = Duration::from_millis(20);
+        let config = GuardConfig {
+            poll_interval: Some(interval),
+            ..Default::default()
+        };
+        let guard = SyncGuard::new(config);
+        let t_guard = guard.clone();
+        let _t = std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(100));
+            assert!(t_guard.set(std::time::Instant::now()).is_ok());
+        });
+
+        let value = guard.wait();
+        assert!(value.is_ok());
+        assert!(
+            value.unwrap().elapsed() <= interval + Duration::from_millis(EPSILON_MILLIS as u64)
+        );
+    }
+
+    #[test]
+    fn test_wait_with_poll_interval_timed_out() {
+        let timeout = Duration::from_millis(50);
+        let config = GuardConfig {
+            timeout: Timeout::Duration(timeout),
+            poll_interval: Some(Duration::from_millis(10)),
+            ..Default::default()
+        };
+        let guard = SyncGuard::<u8>::new(config);
+
+        let t0 = std::time::Instant::now();
+        assert_eq!(guard.wait().unwrap_err(), GuardError::Timeout);
+        assert!(t0.elapsed() >= timeout);
+        assert!(t0.elapsed() <= timeout + Duration::from_millis(2 * EPSILON_MILLIS as u64));
+    }
+
+    #[test]
+    #[allow(clippy::mutable_key_type)]
+    fn test_guard_identity() {
+        let guard = SyncGuard::<u8>::default();
+        let other = SyncGuard::<u8>::default();
+
+        let mut guards = std::collections::HashSet::new();
+        guards.insert(guard.clone());
+        guards.insert(guard.clone());
+        assert_eq!(guards.len(), 1);
+        assert!(guards.contains(&guard));
+        assert!(!guards.contains(&other));
+        assert!(guard != other);
+    }
+
+    #[test]
+    fn test_on_poll_hook() {
+        let observed = Arc::new(parking_lot::Mutex::new(Vec::new()));
+        let t_observed = observed.clone();
+        let config = GuardConfig {
+            poll_interval: Some(Duration::from_millis(10)),
+            on_poll: Some(Arc::new(move |state| t_observed.lock().push(*state))),
+            ..Default::default()
+        };
+        let guard = SyncGuard::new(config);
+        let t_guard = guard.clone();
+        let _t = std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(50));
+            assert!(t_guard.set(42u8).is_ok());
+        });
+
+        assert!(guard.wait().is_ok());
+        let polls = {
+            let observed = observed.lock();
+            assert!(observed.len() > 1);
+            assert_eq!(observed.first(), Some(&GuardState::UnSet));
+            assert_eq!(observed.last(), Some(&GuardState::Set));
+            observed.len()
+        };
+
+        // the hook follows the config of each handle
+        let unhooked = guard.map_config(|config| GuardConfig {
+            on_poll: None,
+            ..config
+        });
+        assert!(unhooked.wait().is_ok());
+        assert_eq!(observed.lock().len(), polls);
+        assert!(guard.wait().is_ok());
+        assert_eq!(observed.lock().len(), polls + 1);
+    }
+
+    #[test]
+    fn test_wait_short_timeout_precision() {
+        let timeout = Duration::from_millis(2);
+        let config = GuardConfig {
+            timeout: Timeout::Duration(timeout),
+            poll_interval: Some(Duration::from_millis(50)),
+            ..Default::default()
+        };
+        let guard = SyncGuard::<u8>::new(config);
+
+        let t0 = std::time::Instant::now();
+        assert_eq!(guard.wait(), Err(GuardError::Timeout));
+        let elapsed = t0.elapsed();
+        assert!(elapsed >= timeout);
+        assert!(elapsed < timeout + Duration::from_millis(5), "{elapsed:?}");
+    }
+
+    #[test]
+    fn test_effective_deadline() {
+        let timeout = Duration::from_millis(500);
+        let guard = SyncGuard::<u8>::new(GuardConfig {
+            timeout: Timeout::Duration(timeout),
+            ..Default::default()
+        });
+        let expected = std::time::Instant::now() + timeout;
+        let deadline = guard.effective_deadline().unwrap();
+        assert!(deadline >= expected);
+        assert!(deadline - expected < Duration::from_millis(EPSILON_MILLIS as u64));
+
+        guard.set_timeout(Timeout::Infinite);
+        assert_eq!(guard.effective_deadline(), None);
+        guard.set_timeout(Timeout::Instant);
+        assert_eq!(guard.effective_deadline(), None);
+    }
+
+    #[test]
+    fn test_wait_attempts() {
+        let polls = Arc::new(AtomicUsize::new(0));
+        let t_polls = polls.clone();
+        let config = GuardConfig {
+            timeout: Timeout::Attempts(5),
+            poll_interval: Some(Duration::from_millis(1)),
+            on_poll: Some(Arc::new(move |_| {
+                t_polls.fetch_add(1, Ordering::SeqCst);
+            })),
+            ..Default::default()
+        };
+        let guard = SyncGuard::<u8>::new(config);
+
+        assert_eq!(guard.wait(), Err(GuardError::Timeout));
+        assert_eq!(polls.load(Ordering::SeqCst), 5);
+    }
+
+    #[test]
+    fn test_max_waiters() {
+        let config = GuardConfig {
+            max_waiters: Some(2),
+            ..Default::default()
+        };
+        let guard = SyncGuard::<u8>::new(config);
+        let waiters: Vec<_> = (0..2)
+            .map(|_| {
+                let t_guard = guard.clone();
+                std::thread::spawn(move || t_guard.wait().is_ok())
+            })
+            .collect();
+        while guard.waiter_count() < 2 {
+            std::thread::yield_now();
+        }
+
+        assert_eq!(guard.wait().unwrap_err(), GuardError::TooManyWaiters);
+        assert!(guard.set(42).is_ok());
+        for waiter in waiters {
+            assert!(waiter.join().expect("failed to wait guard thread"));
+        }
+        assert_eq!(guard.waiter_count(), 0);
+    }
+
+    #[test]
+    fn test_wait_any() {
+        let guards: Vec<_> = (0..3).map(|_| SyncGuard::<u8>::default()).collect();
+        let t_guard = guards[1].clone();
+        let _t = std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(50));
+            assert!(t_guard.set(42).is_ok());
+        });
+
+        let (idx, value) =
+            wait_any(&guards, Timeout::Duration(Duration::from_millis(200))).unwrap();
+        assert_eq!(idx, 1);
+        assert_eq!(*value, 42);
+    }
+
+    #[test]
+    fn test_wait_any_all_killed_or_timed_out() {
+        let guards = vec![SyncGuard::<u8>::default(), SyncGuard::<u8>::default()];
+        let timeout = Timeout::Duration(Duration::from_millis(20));
+        assert!(guards[0].kill().is_ok());
+        assert_eq!(wait_any(&guards, timeout).unwrap_err(), GuardError::Timeout);
+
+        assert!(guards[1].kill().is_ok());
+        assert_eq!(wait_any(&guards, timeout).unwrap_err(), GuardError::Killed);
+    }
+
+    #[test]
+    fn test_wait_any_like_a_wait() {
+        let guards = vec![
+            SyncGuard::<u8>::default(),
+            SyncGuard::from_fn(|| 42, GuardConfig::default()),
+        ];
+        let min = Duration::from_millis(30);
+        let t0 = std::time::Instant::now();
+        let (idx, value) = wait_any(&guards, Timeout::AtLeast(min)).unwrap();
+        assert_eq!((idx, *value), (1, 42));
+        assert!(t0.elapsed() >= min);
+
+        let guards = vec![SyncGuard::<u8>::default()];
+        let err = std::io::Error::other("upstream down");
+        assert!(guards[0].kill_with_error(err).is_ok());
+        assert_eq!(
+            wait_any(&guards, Timeout::Infinite).unwrap_err(),
+            GuardError::Failed(String::from("upstream down"))
+        );
+
+        let shutdown = Arc::new(AtomicBool::new(true));
+        let guards = vec![SyncGuard::<u8>::new(GuardConfig {
+            shutdown: Some(shutdown),
+            ..Default::default()
+        })];
+        assert_eq!(
+            wait_any(&guards, Timeout::Infinite).unwrap_err(),
+            GuardError::Cancelled
+        );
+    }
+
+    #[test]
+    fn test_wait_all() {
+        let guards: Vec<_> = (0..3).map(|_| SyncGuard::<u8>::default()).collect();
+        let t_guards = guards.clone();
+        let _t = std::thread::spawn(move || {
+            for (idx, guard) in t_guards.iter().enumerate().rev() {
+                std::thread::sleep(Duration::from_millis(20));
+                assert!(guard.set(idx as u8).is_ok());
+            }
+        });
+
+        let timeout = Timeout::Duration(Duration::from_millis(200));
+        let t0 = std::time::Instant::now();
+        let values = wait_all(&guards, timeout).unwrap();
+        assert!(t0.elapsed().as_millis() >= 60);
+        assert_eq!(
+            values.iter().map(|v| **v).collect::<Vec<_>>(),
+            vec![0, 1, 2]
+        );
+
+        let guards: Vec<_> = (0..2).map(|_| SyncGuard::<u8>::default()).collect();
+        let timeout = Timeout::Duration(Duration::from_millis(20));
+        assert!(guards[0].set(1).is_ok());
+        assert_eq!(wait_all(&guards, timeout).unwrap_err(), GuardError::Timeout);
+        assert!(guards[1].kill().is_ok());
+        assert_eq!(wait_all(&guards, timeout).unwrap_err(), GuardError::Killed);
+    }
+
+    #[test]
+    fn test_wait_all_like_a_wait() {
+        let guards = vec![
+            SyncGuard::from_fn(|| 1u8, GuardConfig::default()),
+            SyncGuard::from_fn(|| 2u8, GuardConfig::default()),
+        ];
+        let values = wait_all(&guards, Timeout::Instant).unwrap();
+        assert_eq!(values.iter().map(|v| **v).collect::<Vec<_>>(), vec![1, 2]);
+
+        let shutdown = Arc::new(AtomicBool::new(false));
+        let guards = vec![SyncGuard::<u8>::new(GuardConfig {
+            shutdown: Some(shutdown.clone()),
+            ..Default::default()
+        })];
+        let t_shutdown = shutdown.clone();
+        let _t = std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(20));
+            t_shutdown.store(true, Ordering::Release);
+        });
+        assert_eq!(
+            wait_all(&guards, Timeout::Infinite).unwrap_err(),
+            GuardError::Cancelled
+        );
+    }
+
+    #[test]
+    fn test_try_into_inner() {
+        let guard = SyncGuard::<String>::default();
+        assert!(guard.set(String::from("Hello SyncGuard")).is_ok());
+        let clone = guard.clone();
+        assert_eq!(clone.try_into_inner().unwrap().unwrap(), "Hello SyncGuard");
+        assert_eq!(guard.try_into_inner().unwrap().unwrap(), "Hello SyncGuard");
+
+        assert!(SyncGuard::<u8>::default()
+            .try_into_inner()
+            .unwrap()
+            .is_none());
+
+        let guard = SyncGuard::<u8>::default();
+        assert!(guard.kill().is_ok());
+        assert_eq!(guard.try_into_inner().unwrap_err(), GuardError::Killed);
+    }
+
+    #[test]
+    fn test_set_policies() {
+        let guard_with = |set_policy| {
+            SyncGuard::<u8>::new(GuardConfig {
+                timeout: Timeout::Instant,
+                set_policy,
+                ..Default::default()
+            })
+        };
+
+        let guard = guard_with(SetPolicy::Overwrite);
+        assert!(guard.set(1).is_ok());
+        assert!(guard.set(2).is_ok());
+        assert_eq!(*guard.wait().unwrap(), 2);
+
+        let guard = guard_with(SetPolicy::RejectNewer);
+        assert!(guard.set(1).is_ok());
+        assert!(guard.set(2).is_ok());
+        assert_eq!(guard.set_or_reject(3), Err(3));
+        assert_eq!(*guard.wait().unwrap(), 1);
+        assert!(guard.reset().is_ok());
+        assert_eq!(guard.set_or_reject(4), Ok(()));
+        assert_eq!(*guard.wait().unwrap(), 4);
+
+        let guard = guard_with(SetPolicy::Error);
+        assert!(guard.set(1).is_ok());
+        assert_eq!(guard.set(2).unwrap_err(), GuardError::AlreadySet);
+        assert_eq!(guard.set_or_reject(3), Err(3));
+        assert_eq!(*guard.wait().unwrap(), 1);
+    }
+
+    #[test]
+    fn test_wait_timed() {
+        let guard = SyncGuard::default();
+        let t_guard = guard.clone();
+        let _t = std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(100));
+            assert!(t_guard.set(42u8).is_ok());
+        });
+
+        let (value, elapsed) = guard.wait_timed().unwrap();
+        assert_eq!(*value, 42);
+        assert!(elapsed.as_millis() >= 100);
+        assert!(elapsed.as_millis() - 100 <= EPSILON_MILLIS);
+    }
+
+    #[test]
+    fn test_conditional_reset() {
+        let config = GuardConfig {
+            timeout: Timeout::Instant,
+            ..Default::default()
+        };
+        let guard = SyncGuard::<u8>::new(config);
+        assert!(guard.set(42).is_ok());
+
+        assert_eq!(guard.reset_if(|value| *value > 100).unwrap(), None);
+        assert_eq!(*guard.wait().unwrap(), 42);
+
+        assert_eq!(guard.reset_if(|value| *value > 10).unwrap(), Some(42));
+        assert!(guard.wait().is_err());
+        assert_eq!(guard.reset_if(|_| true).unwrap(), None);
+    }
+
+    #[test]
+    fn test_named_guard_debug() {
+        let config = GuardConfig {
+            name: Some("cache".into()),
+            ..Default::default()
+        };
+        let guard = SyncGuard::<u8>::new(config);
+        assert!(guard.set(42).is_ok());
+
+        let debug = format!("{:?}", guard);
+        assert!(debug.contains("name: Some(\"cache\")"));
+        assert!(debug.contains("Set"));
+    }
+
+    #[test]
+    fn test_guard_display() {
+        let config = GuardConfig {
+            name: Some("cache".into()),
+            timeout: Timeout::Duration(Duration::from_millis(500)),
+            ..Default::default()
+        };
+        let guard = SyncGuard::<u8>::new(config);
+        assert!(guard.set(42).is_ok());
+        assert_eq!(
+            guard.to_string(),
+            "SyncGuard(name=cache, state=Set, timeout=500ms, waiters=0)"
+        );
+
+        let _locked = guard.inner.config.write();
+        assert_eq!(guard.to_string(), "SyncGuard(state=Set, waiters=0)");
+    }
+
+    #[test]
+    fn test_wait_with_jittered_backoff() {
+        let config = GuardConfig {
+            wait_strategy: WaitStrategy::Backoff {
+                start: Duration::from_millis(1),
+                max: Duration::from_millis(20),
+                factor: 2.0,
+                jitter: 0.5,
+            },
+            ..Default::default()
+        };
+        let guard = SyncGuard::<u8>::new(config);
+        let waiters: Vec<_> = (0..32)
+            .map(|_| {
+                let guard = guard.clone();
+                std::thread::spawn(move || guard.wait())
+            })
+            .collect();
+
+        std::thread::sleep(Duration::from_millis(50));
+        assert!(guard.set(42).is_ok());
+        for waiter in waiters {
+            assert_eq!(*waiter.join().expect("failed to join waiter").unwrap(), 42);
+        }
+    }
+
+    #[test]
+    fn test_wait_with_backoff() {
+        let polls = Arc::new(AtomicUsize::new(0));
+        let t_polls = polls.clone();
+        let config = GuardConfig {
+            wait_strategy: WaitStrategy::Backoff {
+                start: Duration::from_millis(1),
+                max: Duration::from_millis(50),
+                factor: 2.0,
+                jitter: 0.0,
+            },
+            on_poll: Some(Arc::new(move |_| {
+                t_polls.fetch_add(1, Ordering::SeqCst);
+            })),
+            ..Default::default()
+        };
+
+        let guard = SyncGuard::new(config.clone());
+        let t_guard = guard.clone();
+        let _t = std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(10));
+            assert!(t_guard.set(std::time::Instant::now()).is_ok());
+        });
+        let value = guard.wait().unwrap();
+        assert!(value.elapsed() <= Duration::from_millis(10));
+
+        polls.store(0, Ordering::SeqCst);
+        let guard = SyncGuard::new(config);
+        let t_guard = guard.clone();
+        let _t = std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(300));
+            assert!(t_guard.set(42u8).is_ok());
+        });
+        assert!(guard.wait().is_ok());
+        assert!(polls.load(Ordering::SeqCst) < 20);
+    }
+
+    #[test]
+    fn test_wait_cancelled_by_shutdown_flag() {
+        let shutdown = Arc::new(AtomicBool::new(false));
+        let config = GuardConfig {
+            shutdown: Some(shutdown.clone()),
+            ..Default::default()
+        };
+        let guard = SyncGuard::<u8>::new(config);
+        let t_guard = guard.clone();
+        let t = std::thread::spawn(move || t_guard.wait());
+
+        std::thread::sleep(Duration::from_millis(50));
+        shutdown.store(true, Ordering::Release);
+        let result = t.join().expect("failed to wait guard thread");
+        assert_eq!(result.unwrap_err(), GuardError::Cancelled);
+        assert_eq!(guard.wait().unwrap_err(), GuardError::Cancelled);
+    }
+
+    #[test]
+    fn test_wait_ref_timeout() {
+        let guard = SyncGuard::<String>::default();
+        let timeout = Duration::from_millis(50);
+        assert_eq!(
+            guard.wait_ref_timeout(timeout).err(),
+            Some(GuardError::Timeout)
+        );
+
+        let t_guard = guard.clone();
+        let _t = std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(20));
+            assert!(t_guard.set(String::from("Hello SyncGuard")).is_ok());
+        });
+        let value = guard.wait_ref_timeout(timeout).unwrap();
+        assert_eq!(value.len(), 15);
+        assert_eq!(&*value, "Hello SyncGuard");
+    }
+
+    #[test]
+    fn test_wait_ref_timeout_goes_through_wait() {
+        let timeout = Duration::from_millis(50);
+        let guard = SyncGuard::from_fn(|| String::from("lazy"), GuardConfig::default());
+        assert_eq!(&*guard.wait_ref_timeout(timeout).unwrap(), "lazy");
+
+        let guard = SyncGuard::<String>::new(GuardConfig {
+            notify_policy: NotifyPolicy::One,
+            ..Default::default()
+        });
+        assert!(guard.set(String::from("once")).is_ok());
+        // the value is handed to this waiter alone, so it is held by the reference
+        assert_eq!(&*guard.wait_ref_timeout(timeout).unwrap(), "once");
+        assert!(!guard.is_set());
+        assert_eq!(guard.stats().resolved, 1);
+    }
+
+    #[cfg(feature = "crossbeam")]
+    #[test]
+    fn test_ready_receiver_select() {
+        let guard = SyncGuard::<u32>::default();
+        let ready = guard.ready_receiver();
+        let (_tx, rx) = crossbeam_channel::unbounded::<&str>();
+
+        let t_guard = guard.clone();
+        let _t = std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(20));
+            assert!(t_guard.set(42).is_ok());
+        });
+
+        crossbeam_channel::select! {
+            recv(ready) -> value => assert_eq!(value.as_deref(), Ok(&42)),
+            recv(rx) -> _ => panic!("channel branch must not fire"),
+            default(Duration::from_millis(500)) => panic!("guard branch did not fire"),
+        }
+        assert_eq!(guard.ready_receiver().recv().as_deref(), Ok(&42));
+
+        let guard = SyncGuard::<u32>::default();
+        let ready = guard.ready_receiver();
+        assert!(guard.kill().is_ok());
+        assert!(ready.recv().is_err());
+        assert!(guard.ready_receiver().recv().is_err());
+    }
+
+    #[test]
+    fn test_get() {
+        let guard = SyncGuard::<u8>::default();
+        assert_eq!(guard.get(), None);
+        assert!(guard.set(42).is_ok());
+        assert_eq!(guard.get(), Some(Arc::new(42)));
+        assert!(guard.force_kill().is_ok());
+        assert_eq!(guard.get(), None);
+    }
+
+    #[test]
+    fn test_clone_empty() {
+        let config = GuardConfig {
+            timeout: Timeout::Instant,
+            ..Default::default()
+        };
+        let guard = SyncGuard::<u8>::new(config);
+        let (clone, sibling) = (guard.clone(), guard.clone_empty());
+        assert_ne!(guard, sibling);
+
+        assert!(guard.set(42).is_ok());
+        assert_eq!(*clone.wait().unwrap(), 42);
+        assert_eq!(sibling.wait().unwrap_err(), GuardError::NotReady);
+
+        assert!(sibling.set(1).is_ok());
+        assert_eq!(*guard.wait().unwrap(), 42);
+    }
+
+    #[test]
+    fn test_set_timeout_shared_by_clones() {
+        let guard = SyncGuard::<u8>::default();
+        let t_guard = guard.clone();
+        guard.set_timeout(Timeout::Duration(Duration::from_millis(50)));
+
+        let t0 = std::time::Instant::now();
+        assert_eq!(t_guard.wait(), Err(GuardError::Timeout));
+        assert!(t0.elapsed().as_millis() < 50 + EPSILON_MILLIS);
+    }
+
+    #[test]
+    fn test_snapshot() {
+        let guard = SyncGuard::default();
+        assert_eq!(guard.snapshot().err(), Some(GuardError::NotReady));
+
+        assert!(guard.set(1).is_ok());
+        let snapshot = guard.snapshot().unwrap();
+        assert_eq!(**snapshot.value(), 1);
+        assert!(snapshot.is_current(&guard));
+
+        assert!(guard.set(2).is_ok());
+        assert!(!snapshot.is_current(&guard));
+        assert_eq!(**guard.snapshot().unwrap().value(), 2);
+
+        // same generation, but another guard
+        let other = SyncGuard::default();
+        assert!(other.set(3).is_ok());
+        assert!(other.set(4).is_ok());
+        assert_eq!(other.generation(), guard.generation());
+        assert!(!guard.snapshot().unwrap().is_current(&other));
+        assert!(guard.snapshot().unwrap().is_current(&guard.clone()));
+    }
+
+    #[test]
+    fn test_get_copy() {
+        #[derive(Clone, Copy, Debug, PartialEq)]
+        struct Point {
+            x: i32,
+            y: i32,
+        }
+
+        let guard = SyncGuard::default();
+        assert!(guard.set(Point { x: 1, y: 2 }).is_ok());
+        let point: Point = guard.get_copy().unwrap();
+        assert_eq!(point, Point { x: 1, y: 2 });
+    }
+
+    #[test]
+    fn test_stats() {
+        let config = GuardConfig {
+            timeout: Timeout::Duration(Duration::from_millis(10)),
+            ..Default::default()
+        };
+        let guard = SyncGuard::new(config);
+        assert_eq!(guard.stats(), GuardStats::default());
+
+        assert_eq!(guard.wait(), Err(GuardError::Timeout));
+        assert!(guard.set(1).is_ok());
+        assert!(guard.wait().is_ok());
+        assert!(guard.modify(|val| *val += 1).is_ok());
+        assert_eq!(guard.reset(), Ok(Some(2)));
+        assert!(guard.kill().is_ok());
+        assert_eq!(guard.wait(), Err(GuardError::Killed));
+
+        assert_eq!(
+            guard.clone().stats(),
+            GuardStats {
+                sets: 1,
+                kills: 1,
+                resets: 1,
+                timeouts: 1,
+                resolved: 1,
+                peak_waiters: 1,
+            }
+        );
+    }
+
+    #[test]
+    fn test_cached_consumer() {
+        let guard = SyncGuard::<u8>::default();
+        let mut consumer = CachedConsumer::new(guard.clone());
+        assert_eq!(consumer.try_wait().unwrap_err(), GuardError::NotReady);
+
+        assert!(guard.set(1).is_ok());
+        assert_eq!(*consumer.try_wait().unwrap(), 1);
+        {
+            // served from the cache while the guard is locked for writing
+            let _locked = guard.inner.value.write();
+            assert_eq!(*consumer.try_wait().unwrap(), 1);
+        }
+
+        assert!(guard.set(2).is_ok());
+        assert_eq!(*consumer.try_wait().unwrap(), 2);
+    }
+
+    #[test]
+    fn test_guard_is_send_sync() {
+        fn assert_send_sync<T: Send + Sync>() {}
+
+        assert_send_sync::<SyncGuard<u8>>();
+        assert_send_sync::<SyncGuard<String>>();
+    }
+
+    #[cfg(feature = "tracing")]
+    #[test]
+    #[tracing_test::traced_test]
+    fn test_wait_emits_tracing_span() {
+        let guard = SyncGuard::<u8>::default();
+        assert!(guard.set(42).is_ok());
+        assert!(guard.wait().is_ok());
+
+        assert!(logs_contain("defender.wait"));
+        assert!(logs_contain("timeout=infinite"));
+        assert!(logs_contain("outcome=\"value\""));
+        assert!(logs_contain("elapsed_ms="));
+        assert!(logs_contain("value set"));
+    }
 }
@@ -0,0 +1,206 @@
+use std::collections::BTreeMap;
+use std::sync::OnceLock;
+use std::task::{Poll, Waker};
+use std::time::{Duration, Instant};
+
+use crate::error::GuardError;
+use crate::primitives::{self, Condvar, Mutex};
+use crate::Timeout;
+
+/// Shortest delay an alarm is armed with, so re-checks without a poll interval do not spin the
+/// timer thread.
+const MIN_DELAY: Duration = Duration::from_millis(1);
+/// Time the timer thread lingers without alarms before it exits.
+const IDLE: Duration = Duration::from_secs(1);
+
+/// Wakers due at an instant, woken by a background thread, so a pending future notices its
+/// deadline whatever runtime polls it, without re-polling itself. The thread is started with
+/// the first alarm and exits once it has been idle for a while.
+#[derive(Default)]
+struct Timer {
+    due: Mutex<Due>,
+    changed: Condvar,
+}
+
+#[derive(Default)]
+struct Due {
+    running: bool,
+    next_id: u64,
+    wakers: BTreeMap<(Instant, u64), Waker>,
+}
+
+fn timer() -> &'static Timer {
+    static TIMER: OnceLock<Timer> = OnceLock::new();
+    TIMER.get_or_init(Timer::default)
+}
+
+impl Timer {
+    fn run(&self) {
+        let mut due = self.due.lock();
+        loop {
+            let now = Instant::now();
+            let mut expired = Vec::new();
+            while let Some(entry) = due.wakers.first_entry() {
+                if entry.key().0 > now {
+                    break;
+                }
+                expired.push(entry.remove());
+            }
+            if !expired.is_empty() {
+                // woken without the lock, so a woken task re-arming the timer does not block
+                drop(due);
+                expired.into_iter().for_each(Waker::wake);
+                due = self.due.lock();
+                continue;
+            }
+
+            let timeout = match due.wakers.keys().next() {
+                Some(&(deadline, _)) => deadline.saturating_duration_since(now),
+                None => IDLE,
+            };
+            let timed_out;
+            (due, timed_out) = self.changed.wait_for(due, timeout);
+            if timed_out && due.wakers.is_empty() {
+                due.running = false;
+                return;
+            }
+        }
+    }
+
+    fn insert(
+        &'static self,
+        deadline: Instant,
+        waker: Waker,
+    ) -> Result<(Instant, u64), GuardError> {
+        let mut due = self.due.lock();
+        if !due.running {
+            primitives::spawn("defender-timer", || timer().run())
+                .map_err(|err| GuardError::TimerUnavailable(err.to_string()))?;
+            due.running = true;
+        }
+        due.next_id += 1;
+        let key = (deadline, due.next_id);
+        let earliest = due.wakers.keys().next().is_none_or(|first| key < *first);
+        due.wakers.insert(key, waker);
+        if earliest {
+            self.changed.notify_one();
+        }
+        Ok(key)
+    }
+
+    fn remove(&self, key: &(Instant, u64)) {
+        self.due.lock().wakers.remove(key);
+    }
+}
+
+/// Wake-up of a pending future at an instant, re-armed only when the instant or the waker
+/// changes and disarmed on drop.
+#[derive(Default)]
+pub(crate) struct Alarm {
+    /// Instant the alarm was asked for, the key it is due under and the waker it wakes.
+    armed: Option<(Instant, (Instant, u64), Waker)>,
+}
+
+impl Alarm {
+    /// Fails with `TimerUnavailable` if the timer thread cannot be started.
+    pub(crate) fn arm(&mut self, at: Instant, waker: &Waker) -> Result<(), GuardError> {
+        let now = Instant::now();
+        if let Some((armed_at, (due, _), armed_waker)) = &self.armed {
+            // a fired alarm is re-armed, the wake-up it was armed for did not settle the wait
+            if *armed_at == at && *due > now && armed_waker.will_wake(waker) {
+                return Ok(());
+            }
+        }
+        self.disarm();
+        let key = timer().insert(at.max(now + MIN_DELAY), waker.clone())?;
+        self.armed = Some((at, key, waker.clone()));
+        Ok(())
+    }
+
+    /// Leaves a wait pending until `at`, or until the guard wakes it when `at` is `None`.
+    pub(crate) fn pending_until<R>(
+        &mut self,
+        at: Option<Instant>,
+        waker: &Waker,
+    ) -> Poll<Result<R, GuardError>> {
+        match at.map(|at| self.arm(at, waker)) {
+            Some(Err(err)) => Poll::Ready(Err(err)),
+            Some(Ok(())) | None => Poll::Pending,
+        }
+    }
+
+    pub(crate) fn disarm(&mut self) {
+        if let Some((_, key, _)) = self.armed.take() {
+            timer().remove(&key);
+        }
+    }
+}
+
+impl Drop for Alarm {
+    fn drop(&mut self) {
+        self.disarm();
+    }
+}
+
+/// Instant a pending wait started at `t0` is re-checked at without a transition of the guard:
+/// its deadline, the next attempt a poll interval later, or the end of its minimum. `None` when
+/// only a transition of the guard resolves the wait.
+pub(crate) fn recheck_at(
+    timeout: Timeout,
+    t0: Instant,
+    poll_interval: Option<Duration>,
+) -> Option<Instant> {
+    match timeout {
+        Timeout::Duration(_) => timeout.deadline(t0),
+        Timeout::Attempts(_) => Some(Instant::now() + poll_interval.unwrap_or(MIN_DELAY)),
+        Timeout::AtLeast(min) => Some(t0 + min).filter(|at| *at > Instant::now()),
+        Timeout::Instant | Timeout::Infinite => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::task::{Wake, Waker};
+    use std::time::{Duration, Instant};
+
+    use crate::timer::{recheck_at, Alarm, MIN_DELAY};
+    use crate::Timeout;
+
+    struct Counter(AtomicUsize);
+
+    impl Wake for Counter {
+        fn wake(self: Arc<Self>) {
+            self.0.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    #[test]
+    fn test_alarm_wakes_once() {
+        let counter = Arc::new(Counter(AtomicUsize::new(0)));
+        let waker = Waker::from(counter.clone());
+        let mut alarm = Alarm::default();
+        let at = Instant::now() + Duration::from_millis(20);
+        assert!(alarm.arm(at, &waker).is_ok());
+        assert!(alarm.arm(at, &waker).is_ok());
+
+        std::thread::sleep(Duration::from_millis(100));
+        assert_eq!(counter.0.load(Ordering::SeqCst), 1);
+
+        let mut dropped = Alarm::default();
+        assert!(dropped
+            .arm(Instant::now() + Duration::from_millis(20), &waker)
+            .is_ok());
+        drop(dropped);
+        std::thread::sleep(Duration::from_millis(100));
+        assert_eq!(counter.0.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn test_attempts_recheck_is_delayed() {
+        let now = Instant::now();
+        let at = recheck_at(Timeout::Attempts(3), now, None).unwrap();
+        assert!(at >= now + MIN_DELAY);
+    }
+}
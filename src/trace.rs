@@ -0,0 +1,28 @@
+use std::time::Instant;
+
+use crate::error::GuardError;
+use crate::GuardConfig;
+
+pub(crate) fn wait_span<T>(guard: *const T, config: &GuardConfig) -> tracing::Span {
+    tracing::debug_span!(
+        "defender.wait",
+        guard = guard as usize,
+        name = config.name.as_deref(),
+        timeout = %config.timeout,
+        outcome = tracing::field::Empty,
+        elapsed_ms = tracing::field::Empty,
+    )
+}
+
+pub(crate) fn record_wait<R>(span: &tracing::Span, result: &Result<R, GuardError>, t0: Instant) {
+    let outcome = match result {
+        Ok(_) => "value",
+        Err(GuardError::Timeout) => "timeout",
+        Err(GuardError::Killed) => "killed",
+        Err(GuardError::Failed(_)) => "failed",
+        Err(_) => "error",
+    };
+    span.record("outcome", outcome);
+    span.record("elapsed_ms", t0.elapsed().as_millis() as u64);
+    span.in_scope(|| tracing::debug!("wait finished"));
+}
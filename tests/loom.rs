@@ -0,0 +1,63 @@
+//! Model checked races of the blocking guard, run with
+//! `RUSTFLAGS="--cfg loom" cargo test --features loom --test loom --release`.
+#![cfg(loom)]
+
+use defender::error::GuardError;
+use defender::sync::SyncGuard;
+use defender::{GuardConfig, GuardState, Timeout};
+
+fn guard() -> SyncGuard<u8> {
+    SyncGuard::new(GuardConfig {
+        timeout: Timeout::Infinite,
+        ..Default::default()
+    })
+}
+
+#[test]
+fn test_set_wakes_waiter() {
+    loom::model(|| {
+        let guard = guard();
+        let t_guard = guard.clone();
+        let waiter = loom::thread::spawn(move || t_guard.wait());
+
+        assert!(guard.set(42).is_ok());
+        assert_eq!(*waiter.join().unwrap().unwrap(), 42);
+        assert_eq!(guard.state(), GuardState::Set);
+    });
+}
+
+#[test]
+fn test_kill_wakes_waiter() {
+    loom::model(|| {
+        let guard = guard();
+        let t_guard = guard.clone();
+        let waiter = loom::thread::spawn(move || t_guard.wait());
+
+        assert!(guard.kill().is_ok());
+        assert!(matches!(waiter.join().unwrap(), Err(GuardError::Killed)));
+        assert_eq!(guard.state(), GuardState::Killed);
+    });
+}
+
+#[test]
+fn test_kill_races_set() {
+    loom::model(|| {
+        let guard = guard();
+        let t_guard = guard.clone();
+        let killer = loom::thread::spawn(move || t_guard.kill());
+
+        let set = guard.set(42);
+        let kill = killer.join().unwrap();
+        match (set, kill) {
+            (Ok(()), Err(GuardError::UnableToKilled)) => {
+                assert_eq!(guard.state(), GuardState::Set);
+                assert_eq!(guard.get().as_deref(), Some(&42));
+            }
+            (Err(GuardError::Killed), Ok(())) => {
+                assert_eq!(guard.state(), GuardState::Killed);
+                assert_eq!(guard.get(), None);
+            }
+            outcome => panic!("invalid outcome of racing set and kill: {outcome:?}"),
+        }
+    });
+}
@@ -0,0 +1,5 @@
+#[test]
+fn test_unused_results_warn() {
+    let t = trybuild::TestCases::new();
+    t.compile_fail("tests/ui/unused_*.rs");
+}
@@ -1,16 +1,28 @@
 use defender::error::GuardError;
 use defender::sync::SyncGuard;
-use defender::{GuardConfig, Timeout};
+use defender::{GuardConfig, NotifyPolicy, SetPolicy, Timeout, WaitStrategy};
 
 const EPSILON_MILLIS: u128 = 10;
 const TEST_CONFIG: GuardConfig = GuardConfig {
+    name: None,
     timeout: Timeout::Duration(std::time::Duration::from_millis(100)),
+    poll_interval: None,
+    wait_strategy: WaitStrategy::Fixed,
+    on_poll: None,
+    max_waiters: None,
+    set_policy: SetPolicy::Overwrite,
+    notify_policy: NotifyPolicy::All,
+    shutdown: None,
+    slow_wait_threshold: None,
+    on_slow_wait: None,
+    serve_stale_on_timeout: false,
+    history_capacity: 16,
 };
 
 #[test]
 fn test_sync_guard() {
     let guard = SyncGuard::<String>::new(TEST_CONFIG);
-    let mut guard_clone = guard.clone();
+    let guard_clone = guard.clone();
 
     let guard_thread = std::thread::spawn(move || {
         std::thread::sleep(std::time::Duration::from_millis(60));
@@ -53,3 +65,50 @@ async fn test_sync_guard_in_async_context() {
     assert!(!sleep_timeout, "tokio sleep should not polled");
     assert!(guard_timeout, "guard should block async sleep");
 }
+
+#[test]
+fn test_sync_guard_set_not_starved_by_waiters() {
+    let guard = SyncGuard::<u64>::new(GuardConfig {
+        timeout: Timeout::Infinite,
+        ..TEST_CONFIG
+    });
+    let waiters: Vec<_> = (0..64)
+        .map(|_| {
+            let guard = guard.clone();
+            std::thread::spawn(move || *guard.wait().unwrap())
+        })
+        .collect();
+    while guard.waiter_count() < 64 {
+        std::thread::yield_now();
+    }
+
+    let setter = guard.clone();
+    let t0 = std::time::Instant::now();
+    let set_thread = std::thread::spawn(move || {
+        assert!(setter.set(42).is_ok());
+        t0.elapsed()
+    });
+
+    let elapsed = set_thread.join().expect("Error while joining threads");
+    assert!(elapsed.as_millis() < 1000);
+    for waiter in waiters {
+        assert_eq!(waiter.join().expect("Error while joining threads"), 42);
+    }
+}
+
+#[test]
+fn test_sync_guard_attempts_timeout() {
+    let polls = std::sync::Arc::new(std::sync::atomic::AtomicU32::new(0));
+    let t_polls = polls.clone();
+    let guard = SyncGuard::<u64>::new(GuardConfig {
+        timeout: Timeout::Attempts(1000),
+        on_poll: Some(std::sync::Arc::new(move |_| {
+            t_polls.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+        })),
+        ..TEST_CONFIG
+    });
+
+    // spinning without a poll interval, the wait ends by counting, not by the clock
+    assert!(matches!(guard.wait(), Err(GuardError::Timeout)));
+    assert_eq!(polls.load(std::sync::atomic::Ordering::SeqCst), 1000);
+}
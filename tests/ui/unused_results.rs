@@ -0,0 +1,13 @@
+#![deny(unused_must_use)]
+
+use defender::sync::SyncGuard;
+
+fn main() {
+    let guard = SyncGuard::<u8>::default();
+    guard.set(42);
+    guard.kill();
+    guard.reset();
+    guard.wait();
+    guard.map_config(|config| config);
+    guard.clone_empty();
+}